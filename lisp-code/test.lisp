@@ -2,7 +2,7 @@
     (print " "))
 ; test
 (print "variable declaration")
-(defvar PI (+ 2 1.14159))
+(defconstant PI (+ 2 1.14159))
 (print "PI = " PI)
 (print " ")
 
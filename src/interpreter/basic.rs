@@ -1,9 +1,11 @@
-use std::cmp::{PartialEq, PartialOrd};
+use super::bignum::BigInt;
+use super::ratio::Ratio;
+use crate::parser::LispValue;
+use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::default::Default;
-use std::fmt::Display;
 use std::ops::{AddAssign, DivAssign, MulAssign, SubAssign};
 
-pub fn perform_add<T>(numbers: &Vec<T>) -> T
+pub fn perform_add<T>(numbers: &[T]) -> T
 where
     T: AddAssign + Default + Copy,
 {
@@ -19,7 +21,7 @@ where
     result
 }
 
-pub fn perform_sub<T>(numbers: &Vec<T>) -> T
+pub fn perform_sub<T>(numbers: &[T]) -> T
 where
     T: SubAssign + Default + Copy,
 {
@@ -35,7 +37,7 @@ where
     result
 }
 
-pub fn perform_mul<T>(numbers: &Vec<T>) -> T
+pub fn perform_mul<T>(numbers: &[T]) -> T
 where
     T: MulAssign + Default + Copy,
 {
@@ -51,7 +53,7 @@ where
     result
 }
 
-pub fn perform_div<T>(numbers: &Vec<T>) -> T
+pub fn perform_div<T>(numbers: &[T]) -> T
 where
     T: DivAssign + Default + Copy,
 {
@@ -67,108 +69,184 @@ where
     result
 }
 
-pub fn perform_grater_then<T>(arguments: &Vec<T>) -> bool
-where
-    T: PartialOrd,
-{
-    if arguments.is_empty() {
-        return false;
+pub fn to_float(value: &LispValue) -> f64 {
+    match value {
+        LispValue::Int(i) => *i as f64,
+        LispValue::BigInt(i) => i.to_f64(),
+        LispValue::Ratio(r) => r.to_f64(),
+        LispValue::Float(f) => *f,
+        LispValue::SingleFloat(f) => *f as f64,
+        _ => 0.0,
     }
+}
 
-    let mut previous = &arguments[0];
-    for v in &arguments[1..] {
-        if previous <= v {
-            return false;
-        }
-        previous = v;
+pub fn is_zero(value: &LispValue) -> bool {
+    match value {
+        LispValue::Int(i) => *i == 0,
+        LispValue::Float(_) | LispValue::SingleFloat(_) => to_float(value) == 0.0,
+        _ => false,
     }
-    return true;
 }
 
-pub fn perform_equals<T>(arguments: &Vec<T>) -> bool
-where
-    T: PartialEq,
-{
-    if arguments.is_empty() {
-        return false;
+pub fn to_bignum(value: &LispValue) -> Option<BigInt> {
+    match value {
+        LispValue::Int(i) => Some(BigInt::from(*i)),
+        LispValue::BigInt(i) => Some(i.clone()),
+        _ => None,
     }
+}
 
-    let mut previous = &arguments[0];
-    for v in &arguments[1..] {
-        if previous != v {
-            return false;
-        }
-        previous = v;
+/// Folds integers with `small` while the results fit in an `i64`, and with
+/// `big` once one overflows. Returns None when an operation has no result.
+pub fn fold_integers(
+    numbers: &[LispValue],
+    small: fn(i64, i64) -> Option<i64>,
+    big: fn(&BigInt, &BigInt) -> Option<BigInt>,
+) -> Option<LispValue> {
+    if numbers.is_empty() {
+        return Some(LispValue::Int(0));
+    }
+
+    let mut result = numbers[0].clone();
+    for v in &numbers[1..] {
+        result = match (&result, v) {
+            (LispValue::Int(a), LispValue::Int(b)) => match small(*a, *b) {
+                Some(value) => LispValue::Int(value),
+                None => LispValue::integer(big(&BigInt::from(*a), &BigInt::from(*b))?),
+            },
+            (a, b) => LispValue::integer(big(&to_bignum(a)?, &to_bignum(b)?)?),
+        };
     }
-    return true;
+
+    Some(result)
 }
 
-pub fn perform_not_equals<T>(arguments: &Vec<T>) -> bool
-where
-    T: PartialEq,
-{
-    if arguments.is_empty() {
-        return false;
+pub fn to_ratio(value: &LispValue) -> Option<Ratio> {
+    match value {
+        LispValue::Ratio(ratio) => Some(ratio.clone()),
+        value => Some(Ratio::from_integer(to_bignum(value)?)),
     }
+}
 
-    for i in 0..(arguments.len() - 1) {
-        let val = &arguments[i];
-        for v in &arguments[(i + 1)..] {
-            if val == v {
-                return false;
-            }
-        }
+/// Folds rational numbers with `operation`. Returns None when an operation
+/// has no result.
+pub fn fold_rationals(
+    numbers: &[LispValue],
+    operation: fn(&Ratio, &Ratio) -> Option<Ratio>,
+) -> Option<LispValue> {
+    if numbers.is_empty() {
+        return Some(LispValue::Int(0));
     }
-    return true;
+
+    let mut result = to_ratio(&numbers[0])?;
+    for v in &numbers[1..] {
+        result = operation(&result, &to_ratio(v)?)?;
+    }
+
+    Some(LispValue::rational(result))
 }
 
-pub fn perform_less_then<T>(arguments: &Vec<T>) -> bool
-where
-    T: PartialOrd,
-{
-    if arguments.is_empty() {
-        return false;
+/// Applies the operation matching the type of a number. Integers that
+/// overflow `small` go through `big`.
+pub fn map_number(
+    value: &LispValue,
+    small: fn(i64) -> Option<i64>,
+    big: fn(&BigInt) -> BigInt,
+    ratio: fn(&Ratio) -> Ratio,
+    float: fn(f64) -> f64,
+) -> Option<LispValue> {
+    match value {
+        LispValue::Int(i) => match small(*i) {
+            Some(result) => Some(LispValue::Int(result)),
+            None => Some(LispValue::integer(big(&BigInt::from(*i)))),
+        },
+        LispValue::BigInt(i) => Some(LispValue::integer(big(i))),
+        LispValue::Ratio(r) => Some(LispValue::rational(ratio(r))),
+        LispValue::Float(f) => Some(LispValue::Float(float(*f))),
+        LispValue::SingleFloat(f) => Some(LispValue::SingleFloat(float(*f as f64) as f32)),
+        _ => None,
     }
+}
 
-    let mut previous = &arguments[0];
-    for v in &arguments[1..] {
-        if previous >= v {
-            return false;
+fn is_float(value: &LispValue) -> bool {
+    matches!(value, LispValue::Float(_) | LispValue::SingleFloat(_))
+}
+
+/// Compares two reals exactly. A float compared with a rational is
+/// converted to its exact rational value first, so no precision is lost to
+/// the float. Infinities are beyond every rational and NaN is unordered.
+pub fn compare_numbers(a: &LispValue, b: &LispValue) -> Option<Ordering> {
+    match (a, b) {
+        (LispValue::Int(a), LispValue::Int(b)) => Some(a.cmp(b)),
+        (a, b) if is_float(a) && is_float(b) => to_float(a).partial_cmp(&to_float(b)),
+        (a, b) if is_float(b) => compare_numbers(b, a).map(Ordering::reverse),
+        (a, b) if is_float(a) => match Ratio::from_f64(to_float(a)) {
+            Some(ratio) => Some(ratio.cmp(&to_ratio(b)?)),
+            None => to_float(a).partial_cmp(&0.0),
+        },
+        _ => Some(to_ratio(a)?.cmp(&to_ratio(b)?)),
+    }
+}
+
+/// Returns the first number that no other one is ordered `keep` to, which is
+/// the greatest for `Ordering::Greater` and the least for `Ordering::Less`.
+pub fn perform_extreme(numbers: &[LispValue], keep: Ordering) -> Option<LispValue> {
+    let mut result = numbers.first()?;
+    for v in &numbers[1..] {
+        if compare_numbers(v, result) == Some(keep) {
+            result = v;
         }
-        previous = v;
     }
-    return true;
+
+    Some(result.clone())
 }
 
-pub fn perform_print<T>(arguments: &Vec<T>)
+/// Raises `base` to a non-negative power by repeated squaring.
+pub fn perform_power<T>(base: &T, mut exponent: u64, one: T, mul: fn(&T, &T) -> T) -> T
 where
-    T: Display,
+    T: Clone,
 {
-    for v in arguments {
-        print!("{} ", v);
+    let mut result = one;
+    let mut square = base.clone();
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul(&result, &square);
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            square = mul(&square, &square);
+        }
     }
+
+    result
 }
 
-pub fn perform_println<T>(arguments: &Vec<T>)
+/// Tells whether `test` holds for the ordering of every pair of neighbours.
+/// Unordered values, like NaN, fail every test.
+pub fn perform_compare<T>(arguments: &[T], test: fn(Ordering) -> bool) -> bool
 where
-    T: Display,
+    T: PartialOrd,
 {
-    for v in arguments {
-        println!("{} ", v);
-    }
+    arguments
+        .windows(2)
+        .all(|pair| pair[0].partial_cmp(&pair[1]).is_some_and(test))
 }
 
-pub fn perform_print_string(arguments: &Vec<String>) {
-    for v in arguments {
-        let b = v.replace("\\n", "\n");
-        print!("{}", b);
+pub fn perform_not_equals<T>(arguments: &[T]) -> bool
+where
+    T: PartialEq,
+{
+    if arguments.is_empty() {
+        return false;
     }
-}
 
-pub fn perform_println_string(arguments: &Vec<String>) {
-    for v in arguments {
-        let b = v.replace("\\n", "\n");
-        print!("{}", b);
+    for i in 0..(arguments.len() - 1) {
+        let val = &arguments[i];
+        for v in &arguments[(i + 1)..] {
+            if val == v {
+                return false;
+            }
+        }
     }
-    println!();
+    true
 }
@@ -0,0 +1,659 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+const BASE: u64 = 1 << 32;
+
+/// An arbitrary precision integer. The magnitude is stored as base 2^32
+/// digits, least significant first, without leading zero digits. Zero has no
+/// digits and is never negative.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut digits: Vec<u32>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        let negative = negative && !digits.is_empty();
+        Self { negative, digits }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.digits.first().is_some_and(|digit| digit & 1 == 1)
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(false, self.digits.clone())
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+
+        let magnitude = self
+            .digits
+            .iter()
+            .rev()
+            .fold(0u64, |value, &digit| (value << 32) | digit as u64);
+        match self.negative {
+            true if magnitude <= 1 << 63 => Some((magnitude as i64).wrapping_neg()),
+            false if magnitude <= i64::MAX as u64 => Some(magnitude as i64),
+            _ => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .digits
+            .iter()
+            .rev()
+            .fold(0.0, |value, &digit| value * BASE as f64 + digit as f64);
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// Converts the integer part of a float. Returns None for infinities and
+    /// NaN.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        let value = value.trunc();
+        if value.abs() < 9.0e18 {
+            return Some(Self::from(value as i64));
+        }
+
+        // Large floats are integers: mantissa * 2^exponent with exponent > 0.
+        let bits = value.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as u32 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let mantissa = Self::new(value < 0.0, vec![mantissa as u32, (mantissa >> 32) as u32]);
+        Some(mantissa.shl(exponent))
+    }
+
+    /// Multiplies by 2^bits.
+    pub fn shl(&self, bits: u32) -> Self {
+        let mut digits = vec![0; (bits / 32) as usize];
+        digits.extend(shift_left(&self.digits, bits % 32));
+        Self::new(self.negative, digits)
+    }
+
+    /// Divides by 2^bits, rounding toward negative infinity like an
+    /// arithmetic shift.
+    pub fn shr(&self, bits: u32) -> Self {
+        if self.negative {
+            return self.not().shr(bits).not();
+        }
+
+        let skipped = ((bits / 32) as usize).min(self.digits.len());
+        Self::new(false, shift_right(&self.digits[skipped..], bits % 32))
+    }
+
+    /// The bitwise complement in two's complement, which is `-x - 1`.
+    pub fn not(&self) -> Self {
+        self.neg().sub(&Self::from(1))
+    }
+
+    /// Combines the two's complement representations digit by digit, with
+    /// negative integers extended by infinitely many one bits.
+    pub fn bitwise(&self, other: &Self, operation: fn(u32, u32) -> u32) -> Self {
+        let len = self.digits.len().max(other.digits.len()) + 1;
+        let a = self.to_twos_complement(len);
+        let b = other.to_twos_complement(len);
+        let digits = a.iter().zip(&b).map(|(&x, &y)| operation(x, y)).collect();
+        Self::from_twos_complement(digits)
+    }
+
+    /// Tells whether the bit at `index` of the two's complement is set.
+    pub fn bit(&self, index: u64) -> bool {
+        if self.negative {
+            return !self.not().bit(index);
+        }
+
+        match self.digits.get((index / 32) as usize) {
+            Some(digit) => digit >> (index % 32) & 1 == 1,
+            None => false,
+        }
+    }
+
+    /// Number of one bits of a non-negative integer, or of zero bits of a
+    /// negative one.
+    pub fn count_ones(&self) -> u32 {
+        match self.negative {
+            true => self.not().count_ones(),
+            false => self.digits.iter().map(|digit| digit.count_ones()).sum(),
+        }
+    }
+
+    /// Number of bits of the two's complement, not counting the sign.
+    pub fn integer_length(&self) -> u32 {
+        match self.negative {
+            true => self.not().bit_length(),
+            false => self.bit_length(),
+        }
+    }
+
+    fn to_twos_complement(&self, len: usize) -> Vec<u32> {
+        let mut digits = self.digits.clone();
+        digits.resize(len, 0);
+        if self.negative {
+            negate_digits(&mut digits);
+        }
+        digits
+    }
+
+    fn from_twos_complement(mut digits: Vec<u32>) -> Self {
+        let negative = digits.last().is_some_and(|top| top >> 31 == 1);
+        if negative {
+            negate_digits(&mut digits);
+        }
+        Self::new(negative, digits)
+    }
+
+    /// Number of bits of the magnitude.
+    pub fn bit_length(&self) -> u32 {
+        match self.digits.last() {
+            Some(top) => self.digits.len() as u32 * 32 - top.leading_zeros(),
+            None => 0,
+        }
+    }
+
+    /// The greatest integer whose square is not above this one, by Newton's
+    /// method. None for negative integers.
+    pub fn isqrt(&self) -> Option<Self> {
+        if self.negative {
+            return None;
+        }
+        if self.is_zero() {
+            return Some(Self::default());
+        }
+
+        let two = Self::from(2);
+        let mut root = Self::from(1).shl(self.bit_length() / 2 + 1);
+        loop {
+            let (next, _) = root.add(&self.div_rem(&root)?.0).div_rem(&two)?;
+            if next >= root {
+                return Some(root);
+            }
+            root = next;
+        }
+    }
+
+    /// The greatest common divisor, which is never negative.
+    pub fn gcd(&self, other: &Self) -> Self {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while let Some((_, remainder)) = a.div_rem(&b) {
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+
+    /// Reads a decimal integer with an optional sign.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.as_bytes().first()? {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut magnitude = Vec::new();
+        for chunk in digits.as_bytes().chunks(9) {
+            let chunk = std::str::from_utf8(chunk).ok()?;
+            let factor = 10u32.pow(chunk.len() as u32);
+            mul_small_add(&mut magnitude, factor, chunk.parse().ok()?);
+        }
+        Some(Self::new(negative, magnitude))
+    }
+
+    /// Reads an integer in a radix from 2 to 36, with an optional sign.
+    /// Digits above nine are letters of either case.
+    pub fn parse_radix(text: &str, radix: u32) -> Option<Self> {
+        let (negative, digits) = match text.as_bytes().first()? {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, text),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            mul_small_add(&mut magnitude, radix, c.to_digit(radix)?);
+        }
+        Some(Self::new(negative, magnitude))
+    }
+
+    /// Writes the integer in a radix from 2 to 36, with uppercase letters for
+    /// digits above nine.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+
+        let mut digits = Vec::new();
+        let mut magnitude = self.digits.clone();
+        while !magnitude.is_empty() {
+            let (quotient, remainder) = div_rem_small(&magnitude, radix);
+            digits.extend(std::char::from_digit(remainder, radix).map(|c| c.to_ascii_uppercase()));
+            magnitude = quotient;
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.digits.clone())
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_magnitudes(&self.digits, &other.digits));
+        }
+
+        match compare_magnitudes(&self.digits, &other.digits) {
+            Ordering::Less => {
+                Self::new(other.negative, sub_magnitudes(&other.digits, &self.digits))
+            }
+            _ => Self::new(self.negative, sub_magnitudes(&self.digits, &other.digits)),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let negative = self.negative != other.negative;
+        Self::new(negative, mul_magnitudes(&self.digits, &other.digits))
+    }
+
+    /// Truncating division. The remainder has the sign of the dividend. None
+    /// when dividing by zero.
+    pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (quotient, remainder) = div_rem_magnitudes(&self.digits, &other.digits);
+        let quotient = Self::new(self.negative != other.negative, quotient);
+        Some((quotient, Self::new(self.negative, remainder)))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let magnitude = value.unsigned_abs();
+        Self::new(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.digits, &other.digits),
+            (true, true) => compare_magnitudes(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let mut chunks = Vec::new();
+        let mut magnitude = self.digits.clone();
+        while !magnitude.is_empty() {
+            let (quotient, remainder) = div_rem_small(&magnitude, 1_000_000_000);
+            chunks.push(remainder);
+            magnitude = quotient;
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{}", first)?;
+        }
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(mut digits: Vec<u32>) -> Vec<u32> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+/// Negates fixed width two's complement digits in place.
+fn negate_digits(digits: &mut [u32]) {
+    let mut carry = true;
+    for digit in digits.iter_mut() {
+        let (value, overflow) = (!*digit).overflowing_add(carry as u32);
+        *digit = value;
+        carry = overflow;
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn mul_small_add(digits: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for digit in digits.iter_mut() {
+        let value = *digit as u64 * factor as u64 + carry;
+        *digit = value as u32;
+        carry = value >> 32;
+    }
+    if carry > 0 {
+        digits.push(carry as u32);
+    }
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0;
+    for (i, &digit) in long.iter().enumerate() {
+        let value = digit as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(value as u32);
+        carry = value >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Subtracts `b` from `a`, which must not be smaller.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &digit) in a.iter().enumerate() {
+        let value = digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        result.push(value as u32);
+        borrow = if value < 0 { 1 } else { 0 };
+    }
+    trim(result)
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let value = result[i + j] as u64 + x as u64 * y as u64 + carry;
+            result[i + j] = value as u32;
+            carry = value >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(result)
+}
+
+fn div_rem_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder = 0u64;
+    for (i, &digit) in a.iter().enumerate().rev() {
+        let value = (remainder << 32) | digit as u64;
+        quotient[i] = (value / divisor as u64) as u32;
+        remainder = value % divisor as u64;
+    }
+    (trim(quotient), remainder as u32)
+}
+
+fn shift_left(digits: &[u32], shift: u32) -> Vec<u32> {
+    let mut result = Vec::with_capacity(digits.len() + 1);
+    let mut carry = 0;
+    for &digit in digits {
+        result.push((digit << shift) | carry);
+        carry = if shift == 0 { 0 } else { digit >> (32 - shift) };
+    }
+    result.push(carry);
+    result
+}
+
+fn shift_right(digits: &[u32], shift: u32) -> Vec<u32> {
+    let mut result = Vec::with_capacity(digits.len());
+    for (i, &digit) in digits.iter().enumerate() {
+        let high = match digits.get(i + 1) {
+            Some(next) if shift > 0 => next << (32 - shift),
+            _ => 0,
+        };
+        result.push((digit >> shift) | high);
+    }
+    trim(result)
+}
+
+/// Long division of magnitudes, following Knuth's algorithm D. `v` must not
+/// be zero.
+fn div_rem_magnitudes(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitudes(u, v) == Ordering::Less {
+        return (Vec::new(), u.to_vec());
+    }
+    if v.len() == 1 {
+        let (quotient, remainder) = div_rem_small(u, v[0]);
+        return (quotient, vec![remainder]);
+    }
+
+    // Normalize so the top digit of the divisor has its high bit set, which
+    // keeps the estimated quotient digits at most two too large.
+    let shift = v[v.len() - 1].leading_zeros();
+    let mut vn = shift_left(v, shift);
+    vn.pop();
+    let mut un = shift_left(u, shift);
+
+    let n = v.len();
+    let m = u.len() - n;
+    let mut quotient = vec![0u32; m + 1];
+    for j in (0..=m).rev() {
+        let numerator = ((un[j + n] as u64) << 32) | un[j + n - 1] as u64;
+        let mut qhat = numerator / vn[n - 1] as u64;
+        let mut rhat = numerator % vn[n - 1] as u64;
+        while qhat >= BASE || qhat * vn[n - 2] as u64 > ((rhat << 32) | un[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += vn[n - 1] as u64;
+            if rhat >= BASE {
+                break;
+            }
+        }
+
+        let mut borrow = 0i64;
+        for i in 0..n {
+            let product = qhat * vn[i] as u64;
+            let value = un[i + j] as i64 - borrow - (product & 0xFFFF_FFFF) as i64;
+            un[i + j] = value as u32;
+            borrow = (product >> 32) as i64 - (value >> 32);
+        }
+        let value = un[j + n] as i64 - borrow;
+        un[j + n] = value as u32;
+
+        quotient[j] = qhat as u32;
+        if value < 0 {
+            quotient[j] = quotient[j].wrapping_sub(1);
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = un[i + j] as u64 + vn[i] as u64 + carry;
+                un[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            un[j + n] = un[j + n].wrapping_add(carry as u32);
+        }
+    }
+
+    (trim(quotient), shift_right(&un[..n], shift))
+}
+
+#[cfg(test)]
+mod bignum_test {
+    use super::BigInt;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse(text).expect("Invalid integer")
+    }
+
+    #[test]
+    fn parse_and_print() {
+        let text = "-123456789012345678901234567890";
+        assert_eq!(big(text).to_string(), text);
+        assert_eq!(big("+0042").to_string(), "42");
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(BigInt::parse("12a"), None);
+        assert_eq!(BigInt::from(i64::MIN).to_string(), i64::MIN.to_string());
+        assert_eq!(BigInt::parse_radix("-ff", 16), Some(BigInt::from(-255)));
+        assert_eq!(BigInt::parse_radix("zz", 36), Some(BigInt::from(1295)));
+        assert_eq!(BigInt::parse_radix("102", 2), None);
+        assert_eq!(big("-255").to_string_radix(16), "-FF");
+        assert_eq!(
+            big("18446744073709551616").to_string_radix(2),
+            format!("1{}", "0".repeat(64))
+        );
+        assert_eq!(BigInt::default().to_string_radix(8), "0");
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(big("9223372036854775807").to_i64(), Some(i64::MAX));
+        assert_eq!(big("-9223372036854775808").to_i64(), Some(i64::MIN));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("18446744073709551616").to_f64(), 18446744073709551616.0);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = big("340282366920938463463374607431768211456");
+        let b = big("-18446744073709551617");
+        assert_eq!(
+            a.add(&b).to_string(),
+            "340282366920938463444927863358058659839"
+        );
+        assert_eq!(
+            b.sub(&a).to_string(),
+            "-340282366920938463481821351505477763073"
+        );
+        assert_eq!(
+            a.mul(&b).to_string(),
+            "-6277101735386680764176071790128604879565730051895802724352"
+        );
+        assert!(b < a && a > BigInt::from(i64::MAX));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(BigInt::from_f64(-12.75), Some(BigInt::from(-12)));
+        assert_eq!(BigInt::from_f64(1e20), Some(big("100000000000000000000")));
+        assert_eq!(
+            BigInt::from_f64(-2f64.powi(100)),
+            Some(big("-1267650600228229401496703205376"))
+        );
+        assert_eq!(BigInt::from_f64(f64::INFINITY), None);
+        assert_eq!(BigInt::from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn gcd() {
+        assert_eq!(BigInt::from(12).gcd(&BigInt::from(-18)), BigInt::from(6));
+        assert_eq!(BigInt::from(0).gcd(&BigInt::from(-5)), BigInt::from(5));
+        assert_eq!(BigInt::from(0).gcd(&BigInt::from(0)), BigInt::from(0));
+        assert_eq!(BigInt::from(3).shl(70), big("3541774862152233910272"));
+    }
+
+    #[test]
+    fn isqrt() {
+        assert_eq!(BigInt::from(0).isqrt(), Some(BigInt::from(0)));
+        assert_eq!(BigInt::from(15).isqrt(), Some(BigInt::from(3)));
+        assert_eq!(BigInt::from(16).isqrt(), Some(BigInt::from(4)));
+        assert_eq!(
+            big("100000000000000000000000000000000000000000").isqrt(),
+            Some(big("316227766016837933199"))
+        );
+        assert_eq!(BigInt::from(-4).isqrt(), None);
+        assert_eq!(BigInt::from(255).bit_length(), 8);
+        assert_eq!(big("-18446744073709551616").bit_length(), 65);
+    }
+
+    #[test]
+    fn division() {
+        let a = big("-6277101735386680764176071790128604879565730051895802724359");
+        let b = big("18446744073709551617");
+        let (quotient, remainder) = a.div_rem(&b).unwrap();
+        assert_eq!(
+            quotient.to_string(),
+            "-340282366920938463463374607431768211456"
+        );
+        assert_eq!(remainder.to_string(), "-7");
+        assert_eq!(quotient.mul(&b).add(&remainder), a);
+
+        let a = big("123456789012345678901234567890123456789");
+        let b = big("98765432109876543210");
+        let (quotient, remainder) = a.div_rem(&b).unwrap();
+        assert_eq!(quotient.mul(&b).add(&remainder), a);
+        assert!(remainder < b);
+        assert_eq!(a.div_rem(&BigInt::from(0)), None);
+    }
+
+    #[test]
+    fn bits() {
+        let a = big("-18446744073709551617");
+        let b = big("340282366920938463463374607431768211455");
+        assert_eq!(a.bitwise(&BigInt::from(-1), |x, y| x & y), a);
+        assert_eq!(
+            a.bitwise(&b, |x, y| x & y),
+            big("340282366920938463444927863358058659839")
+        );
+        assert_eq!(
+            BigInt::from(-6).bitwise(&BigInt::from(5), |x, y| x | y),
+            BigInt::from(-1)
+        );
+        assert_eq!(a.bitwise(&a, |x, y| x ^ y), BigInt::default());
+        assert_eq!(a.not(), big("18446744073709551616"));
+        assert_eq!(a.shr(64), BigInt::from(-2));
+        assert_eq!(BigInt::from(-1).shr(100), BigInt::from(-1));
+        assert_eq!(b.shr(200), BigInt::default());
+        assert!(a.bit(1000) && a.bit(0) && !a.bit(64));
+        assert_eq!(b.count_ones(), 128);
+        assert_eq!(a.count_ones(), 1);
+        assert_eq!(b.integer_length(), 128);
+        assert_eq!(BigInt::from(-256).integer_length(), 8);
+    }
+}
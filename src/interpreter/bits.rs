@@ -0,0 +1,190 @@
+use super::bignum::BigInt;
+use super::numbers::integer_arguments;
+use super::types::*;
+use crate::parser::LispValue;
+use std::convert::TryFrom;
+
+fn argument_type(function: &str, position: usize, expected: &str, value: &LispValue) -> LispError {
+    LispError::ArgumentType {
+        function: function.to_string(),
+        position: position + 1,
+        expected: expected.to_string(),
+        value: value.clone(),
+    }
+}
+
+/// Reads a count of bits, which must fit in 32 bits.
+fn bit_count(function: &str, position: usize, value: &LispValue) -> Result<u32, LispError> {
+    match value {
+        LispValue::Int(count) => u32::try_from(*count)
+            .map_err(|_| argument_type(function, position, "(unsigned-byte 32)", value)),
+        _ => Err(argument_type(
+            function,
+            position,
+            "(unsigned-byte 32)",
+            value,
+        )),
+    }
+}
+
+/// Reads a byte specifier made by `byte` into its size and position.
+fn byte_specifier(
+    function: &str,
+    position: usize,
+    value: &LispValue,
+) -> Result<(u32, u32), LispError> {
+    match value {
+        LispValue::DottedList(size, bit) if size.len() == 1 => Ok((
+            bit_count(function, position, &size[0])?,
+            bit_count(function, position, bit)?,
+        )),
+        _ => Err(argument_type(function, position, "byte specifier", value)),
+    }
+}
+
+/// The integer whose lowest `size` bits are set.
+fn ones(size: u32) -> BigInt {
+    BigInt::from(1).shl(size).sub(&BigInt::from(1))
+}
+
+fn and(a: u32, b: u32) -> u32 {
+    a & b
+}
+
+/// Folds integers with a bitwise operation, for `logand`, `logior` and
+/// `logxor`. No integers give `identity`.
+pub fn logical(
+    function: &str,
+    identity: i64,
+    operation: fn(u32, u32) -> u32,
+    args: Vec<LispValue>,
+) -> LispResult {
+    let integers = integer_arguments(function, &args)?;
+    let result = integers
+        .iter()
+        .fold(BigInt::from(identity), |result, integer| {
+            result.bitwise(integer, operation)
+        });
+    Ok(LispValue::integer(result))
+}
+
+pub fn lognot(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let integer = integer_arguments("lognot", &args)?.remove(0);
+    Ok(LispValue::integer(integer.not()))
+}
+
+/// Tells whether two integers have a one bit in common.
+pub fn logtest(args: Vec<LispValue>) -> Result<Option<bool>, LispError> {
+    if args.len() != 2 {
+        return Ok(None);
+    }
+
+    let integers = integer_arguments("logtest", &args)?;
+    Ok(Some(!integers[0].bitwise(&integers[1], and).is_zero()))
+}
+
+/// Shifts an integer left by a positive count or right by a negative one.
+/// Right shifts round toward negative infinity.
+pub fn ash(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let integers = integer_arguments("ash", &args)?;
+    let result = match &args[1] {
+        LispValue::Int(count) if *count < 0 => {
+            integers[0].shr(u32::try_from(count.unsigned_abs()).unwrap_or(u32::MAX))
+        }
+        LispValue::BigInt(count) if count.is_negative() => integers[0].shr(u32::MAX),
+        count => integers[0].shl(bit_count("ash", 1, count)?),
+    };
+    Ok(LispValue::integer(result))
+}
+
+/// Tells whether the bit at an index of an integer is set. Negative integers
+/// have infinitely many one bits.
+pub fn logbitp(args: Vec<LispValue>) -> Result<Option<bool>, LispError> {
+    if args.len() != 2 {
+        return Ok(None);
+    }
+
+    let integers = integer_arguments("logbitp", &args)?;
+    match &args[0] {
+        LispValue::Int(index) if *index >= 0 => Ok(Some(integers[1].bit(*index as u64))),
+        LispValue::BigInt(index) if !index.is_negative() => Ok(Some(integers[1].is_negative())),
+        index => Err(argument_type("logbitp", 0, "(integer 0 *)", index)),
+    }
+}
+
+/// Counts the one bits of a non-negative integer, or the zero bits of a
+/// negative one.
+pub fn logcount(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let integer = integer_arguments("logcount", &args)?.remove(0);
+    Ok(LispValue::Int(integer.count_ones() as i64))
+}
+
+pub fn integer_length(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let integer = integer_arguments("integer-length", &args)?.remove(0);
+    Ok(LispValue::Int(integer.integer_length() as i64))
+}
+
+/// Makes the byte specifier of `size` bits starting at bit `position`,
+/// stored as the pair `(size . position)`.
+pub fn byte(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 2 {
+        return Ok(LispValue::nil());
+    }
+
+    bit_count("byte", 0, &args[0])?;
+    bit_count("byte", 1, &args[1])?;
+    Ok(LispValue::DottedList(
+        vec![args[0].clone()],
+        Box::new(args[1].clone()),
+    ))
+}
+
+/// Extracts the bits of a byte specifier from an integer.
+pub fn ldb(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let (size, position) = byte_specifier("ldb", 0, &args[0])?;
+    let integer = integer_arguments("ldb", &args[1..])
+        .map_err(|_| argument_type("ldb", 1, "integer", &args[1]))?
+        .remove(0);
+    Ok(LispValue::integer(
+        integer.shr(position).bitwise(&ones(size), and),
+    ))
+}
+
+/// Replaces the bits of a byte specifier in an integer with the low bits of
+/// a new byte.
+pub fn dpb(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 3 {
+        return Ok(LispValue::nil());
+    }
+
+    let (size, position) = byte_specifier("dpb", 1, &args[1])?;
+    let new_byte = integer_arguments("dpb", &args[..1])?.remove(0);
+    let integer = integer_arguments("dpb", &args[2..])
+        .map_err(|_| argument_type("dpb", 2, "integer", &args[2]))?
+        .remove(0);
+
+    let mask = ones(size).shl(position);
+    let kept = integer.bitwise(&mask.not(), and);
+    let inserted = new_byte.shl(position).bitwise(&mask, and);
+    Ok(LispValue::integer(kept.bitwise(&inserted, |a, b| a | b)))
+}
@@ -0,0 +1,219 @@
+use super::basic::*;
+use super::floats::{result_format, FloatFormat};
+use super::numbers::*;
+use super::types::*;
+use crate::parser::LispValue;
+
+/// A complex number. Both parts are rationals or both are floats, and the
+/// imaginary part of a rational complex is never zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Complex {
+    pub real: LispValue,
+    pub imag: LispValue,
+}
+
+impl LispValue {
+    /// A complex number from two reals. A float part makes both parts floats
+    /// of the same format, and a rational complex with a zero imaginary part
+    /// is its real part.
+    pub fn complex(real: LispValue, imag: LispValue) -> LispValue {
+        match (&real, &imag) {
+            (LispValue::Float(_) | LispValue::SingleFloat(_), _)
+            | (_, LispValue::Float(_) | LispValue::SingleFloat(_)) => {
+                let format = result_format(&[real.clone(), imag.clone()]);
+                let real = format.float(to_float(&real));
+                let imag = format.float(to_float(&imag));
+                LispValue::Complex(Box::new(Complex { real, imag }))
+            }
+            (_, imag) if is_zero(imag) => real,
+            _ => LispValue::Complex(Box::new(Complex { real, imag })),
+        }
+    }
+}
+
+/// The real and imaginary parts of a number. Reals have a zero imaginary
+/// part of their own type.
+pub fn parts(value: &LispValue) -> (LispValue, LispValue) {
+    match value {
+        LispValue::Complex(complex) => (complex.real.clone(), complex.imag.clone()),
+        LispValue::Float(_) => (value.clone(), LispValue::Float(0.0)),
+        LispValue::SingleFloat(_) => (value.clone(), LispValue::SingleFloat(0.0)),
+        _ => (value.clone(), LispValue::Int(0)),
+    }
+}
+
+/// The float format of a number's parts, which is double for rationals.
+fn float_format(values: &[&LispValue]) -> FloatFormat {
+    let parts: Vec<LispValue> = values
+        .iter()
+        .flat_map(|value| {
+            let (real, imag) = parts(value);
+            [real, imag]
+        })
+        .collect();
+    result_format(&parts)
+}
+
+fn from_floats(format: FloatFormat, real: f64, imag: f64) -> LispValue {
+    LispValue::complex(format.float(real), format.float(imag))
+}
+
+fn float_parts(value: &LispValue) -> (f64, f64) {
+    let (real, imag) = parts(value);
+    (to_float(&real), to_float(&imag))
+}
+
+pub fn complex_add(a: &LispValue, b: &LispValue) -> LispResult {
+    let ((ar, ai), (br, bi)) = (parts(a), parts(b));
+    Ok(LispValue::complex(add(vec![ar, br])?, add(vec![ai, bi])?))
+}
+
+pub fn complex_sub(a: &LispValue, b: &LispValue) -> LispResult {
+    let ((ar, ai), (br, bi)) = (parts(a), parts(b));
+    Ok(LispValue::complex(sub(vec![ar, br])?, sub(vec![ai, bi])?))
+}
+
+pub fn complex_mul(a: &LispValue, b: &LispValue) -> LispResult {
+    let ((ar, ai), (br, bi)) = (parts(a), parts(b));
+    let real = sub(vec![
+        mul(vec![ar.clone(), br.clone()])?,
+        mul(vec![ai.clone(), bi.clone()])?,
+    ])?;
+    let imag = add(vec![mul(vec![ar, bi])?, mul(vec![ai, br])?])?;
+    Ok(LispValue::complex(real, imag))
+}
+
+pub fn complex_div(a: &LispValue, b: &LispValue) -> LispResult {
+    let ((ar, ai), (br, bi)) = (parts(a), parts(b));
+    let denominator = add(vec![
+        mul(vec![br.clone(), br.clone()])?,
+        mul(vec![bi.clone(), bi.clone()])?,
+    ])?;
+    if is_zero(&denominator) {
+        return Err(LispError::DivisionByZero);
+    }
+
+    let real = add(vec![
+        mul(vec![ar.clone(), br.clone()])?,
+        mul(vec![ai.clone(), bi.clone()])?,
+    ])?;
+    let imag = sub(vec![mul(vec![ai, br])?, mul(vec![ar, bi])?])?;
+    Ok(LispValue::complex(
+        div(vec![real, denominator.clone()])?,
+        div(vec![imag, denominator])?,
+    ))
+}
+
+/// Folds numbers with a complex operation.
+pub fn fold_complex(
+    numbers: &[LispValue],
+    operation: fn(&LispValue, &LispValue) -> LispResult,
+) -> LispResult {
+    let mut result = match numbers.first() {
+        Some(first) => first.clone(),
+        None => return Ok(LispValue::Int(0)),
+    };
+    for v in &numbers[1..] {
+        result = operation(&result, v)?;
+    }
+
+    Ok(result)
+}
+
+/// Tells whether two numbers are equal, comparing complex numbers by parts.
+pub fn complex_equal(a: &LispValue, b: &LispValue) -> bool {
+    let ((ar, ai), (br, bi)) = (parts(a), parts(b));
+    compare_numbers(&ar, &br) == Some(std::cmp::Ordering::Equal)
+        && compare_numbers(&ai, &bi) == Some(std::cmp::Ordering::Equal)
+}
+
+/// The magnitude of a number, which is a float for complex numbers.
+pub fn magnitude(value: &LispValue) -> f64 {
+    let (real, imag) = float_parts(value);
+    real.hypot(imag)
+}
+
+/// The angle of a number from the positive real axis, between -pi and pi.
+pub fn angle(value: &LispValue) -> f64 {
+    let (real, imag) = float_parts(value);
+    imag.atan2(real)
+}
+
+/// The principal square root of a negative real or a complex number.
+pub fn complex_sqrt(value: &LispValue) -> LispValue {
+    let (real, imag) = float_parts(value);
+    let modulus = real.hypot(imag);
+    let root_real = ((modulus + real) / 2.0).sqrt();
+    let root_imag = ((modulus - real) / 2.0).sqrt();
+    let format = float_format(&[value]);
+    match imag.is_sign_negative() {
+        true => from_floats(format, root_real, -root_imag),
+        false => from_floats(format, root_real, root_imag),
+    }
+}
+
+/// The principal natural logarithm of a negative real or a complex number.
+pub fn complex_log(value: &LispValue) -> LispValue {
+    from_floats(float_format(&[value]), magnitude(value).ln(), angle(value))
+}
+
+pub fn complex_exp(value: &LispValue) -> LispValue {
+    let (real, imag) = float_parts(value);
+    let scale = real.exp();
+    from_floats(
+        float_format(&[value]),
+        scale * imag.cos(),
+        scale * imag.sin(),
+    )
+}
+
+/// Raises a number to a power through `exp(power * log(base))`, for powers
+/// that have no exact or real result.
+pub fn complex_expt(base: &LispValue, power: &LispValue) -> LispResult {
+    if is_zero(base) {
+        return Ok(from_floats(float_format(&[base, power]), 0.0, 0.0));
+    }
+    Ok(complex_exp(&complex_mul(power, &complex_log(base))?))
+}
+
+pub fn complex(args: Vec<LispValue>) -> LispResult {
+    let (real, imag) = match args.as_slice() {
+        [real] => (real.clone(), LispValue::Int(0)),
+        [real, imag] => (real.clone(), imag.clone()),
+        _ => return Ok(LispValue::nil()),
+    };
+
+    real_contagion("complex", &args)?;
+    Ok(LispValue::complex(real, imag))
+}
+
+/// Returns the real part of a number for `realpart`, or the imaginary part
+/// for `imagpart`.
+pub fn part(function: &str, imaginary: bool, args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    contagion(function, &args)?;
+    let (real, imag) = parts(&args[0]);
+    Ok(if imaginary { imag } else { real })
+}
+
+pub fn conjugate(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    contagion("conjugate", &args)?;
+    let (real, imag) = parts(&args[0]);
+    Ok(LispValue::complex(real, sub(vec![imag])?))
+}
+
+pub fn phase(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    contagion("phase", &args)?;
+    Ok(LispValue::Float(angle(&args[0])))
+}
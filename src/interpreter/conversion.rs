@@ -0,0 +1,247 @@
+use super::bignum::BigInt;
+use super::floats::FloatFormat;
+use super::types::*;
+use crate::parser::{self, LispValue, ReadOptions};
+
+pub const PRINT_BASE: &str = "*print-base*";
+pub const PRINT_RADIX: &str = "*print-radix*";
+pub const READ_BASE: &str = "*read-base*";
+pub const READ_DEFAULT_FLOAT_FORMAT: &str = "*read-default-float-format*";
+
+/// Reads a radix, which must be an integer from 2 to 36.
+fn radix_value(function: &str, value: &LispValue) -> Result<u32, LispError> {
+    match value {
+        LispValue::Int(radix) if (2..=36).contains(radix) => Ok(*radix as u32),
+        value => Err(type_error(function, "(integer 2 36)", value.clone())),
+    }
+}
+
+fn radix_variable(state: &State, name: &str) -> Result<u32, LispError> {
+    radix_value(name, &state.get_value(name).unwrap_or_else(LispValue::nil))
+}
+
+/// The float format named by `*read-default-float-format*`.
+fn default_float_format(state: &State) -> Result<FloatFormat, LispError> {
+    let value = state.get_value(READ_DEFAULT_FLOAT_FORMAT);
+    let format = match &value {
+        Some(LispValue::Name(name)) => FloatFormat::from_name(name),
+        _ => None,
+    };
+    match format {
+        Some(format) => Ok(format),
+        None => Err(type_error(
+            READ_DEFAULT_FLOAT_FORMAT,
+            "(member short-float single-float double-float long-float)",
+            value.unwrap_or_else(LispValue::nil),
+        )),
+    }
+}
+
+/// How the reader reads numbers, from `*read-base*` and
+/// `*read-default-float-format*`.
+pub fn read_options(state: &State) -> Result<ReadOptions, LispError> {
+    Ok(ReadOptions {
+        base: radix_variable(state, READ_BASE)?,
+        float_format: default_float_format(state)?,
+    })
+}
+
+/// How `print` writes values, from `*print-base*`, `*print-radix*` and
+/// `*read-default-float-format*`.
+pub fn print_options(state: &State) -> Result<PrintOptions, LispError> {
+    let radix = state
+        .get_value(PRINT_RADIX)
+        .is_some_and(|value| is_true(state, &value));
+    Ok(PrintOptions {
+        base: radix_variable(state, PRINT_BASE)?,
+        radix,
+        escape: false,
+        float_format: default_float_format(state)?,
+    })
+}
+
+/// Reads the keyword arguments of a builtin, giving the value of each of
+/// `keywords` in order. The first value given for a keyword wins.
+fn keyword_arguments(
+    args: &[LispValue],
+    keywords: &[&str],
+) -> Result<Vec<Option<LispValue>>, LispError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(LispError::ArgumentCount {
+            expected: "an even number of keyword arguments".to_string(),
+            given: args.len(),
+        });
+    }
+
+    let mut values = vec![None; keywords.len()];
+    for pair in args.chunks(2) {
+        let position = match &pair[0] {
+            LispValue::Name(name) => keywords.iter().position(|keyword| keyword == name),
+            _ => None,
+        };
+        match position {
+            Some(position) => {
+                values[position].get_or_insert_with(|| pair[1].clone());
+            }
+            None => return Err(LispError::UnknownKeyword(pair[0].clone())),
+        }
+    }
+    Ok(values)
+}
+
+/// Reads an index into a string of `len` characters.
+fn string_index(function: &str, value: &LispValue, len: usize) -> Result<usize, LispError> {
+    match value {
+        LispValue::Int(index) if *index >= 0 && *index as usize <= len => Ok(*index as usize),
+        value => Err(type_error(
+            function,
+            &format!("(integer 0 {})", len),
+            value.clone(),
+        )),
+    }
+}
+
+fn string_argument<'a>(
+    function: &str,
+    args: &'a [LispValue],
+) -> Result<Option<(&'a str, &'a [LispValue])>, LispError> {
+    match args.split_first() {
+        Some((LispValue::String(text), rest)) => Ok(Some((text, rest))),
+        Some((value, _)) => Err(LispError::ArgumentType {
+            function: function.to_string(),
+            position: 1,
+            expected: "string".to_string(),
+            value: value.clone(),
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Reads an integer in a radix from the part of a string between `:start`
+/// and `:end`, allowing whitespace around it. Returns the integer and the
+/// index where reading stopped. With `:junk-allowed`, reading stops at the
+/// first character that is not a digit and gives nil when there are no
+/// digits, instead of signalling a parse error.
+pub fn parse_integer(state: &State, args: Vec<LispValue>) -> LispResult {
+    let (text, keys) = match string_argument("parse-integer", &args)? {
+        Some(argument) => argument,
+        None => return Ok(LispValue::nil()),
+    };
+    let keys = keyword_arguments(keys, &[":start", ":end", ":radix", ":junk-allowed"])?;
+
+    let chars: Vec<char> = text.chars().collect();
+    let start = match &keys[0] {
+        Some(start) => string_index("parse-integer", start, chars.len())?,
+        None => 0,
+    };
+    let end = match &keys[1] {
+        Some(end) if !end.is_nil() => string_index("parse-integer", end, chars.len())?,
+        _ => chars.len(),
+    };
+    if start > end {
+        let expected = format!("(integer 0 {})", end);
+        return Err(type_error(
+            "parse-integer",
+            &expected,
+            LispValue::Int(start as i64),
+        ));
+    }
+    let radix = match &keys[2] {
+        Some(radix) => radix_value("parse-integer", radix)?,
+        None => 10,
+    };
+    let junk_allowed = keys[3].as_ref().is_some_and(|value| is_true(state, value));
+
+    let mut index = start;
+    while index < end && chars[index].is_whitespace() {
+        index += 1;
+    }
+    let number_start = index;
+    if index < end && (chars[index] == '+' || chars[index] == '-') {
+        index += 1;
+    }
+    let digits_start = index;
+    while index < end && chars[index].is_digit(radix) {
+        index += 1;
+    }
+    let number: String = chars[number_start..index].iter().collect();
+    let integer = match index > digits_start {
+        true => BigInt::parse_radix(&number, radix).map(LispValue::integer),
+        false => None,
+    };
+
+    if junk_allowed {
+        let integer = integer.unwrap_or_else(LispValue::nil);
+        return Ok(LispValue::Values(vec![
+            integer,
+            LispValue::Int(index as i64),
+        ]));
+    }
+
+    while index < end && chars[index].is_whitespace() {
+        index += 1;
+    }
+    match integer {
+        Some(integer) if index == end => {
+            Ok(LispValue::Values(vec![integer, LispValue::Int(end as i64)]))
+        }
+        Some(_) => Err(LispError::Parse(format!("junk in string \"{}\"", text))),
+        None => Err(LispError::Parse(format!(
+            "no digits in string \"{}\"",
+            text
+        ))),
+    }
+}
+
+/// Reads the first form of a string in `*read-base*` and
+/// `*read-default-float-format*`. Returns it and the index after it. A
+/// string without a form is an error, unless `eof-error-p` is false, when
+/// `eof-value` is returned instead. A form that is not finished is always
+/// an error.
+pub fn read_from_string(state: &State, args: Vec<LispValue>) -> LispResult {
+    let (text, optionals) = match string_argument("read-from-string", &args)? {
+        Some(argument) => argument,
+        None => return Ok(LispValue::nil()),
+    };
+
+    match parser::read_from_string(text, read_options(state)?).map_err(LispError::Parse)? {
+        Some((value, index)) => Ok(LispValue::Values(vec![value, LispValue::Int(index as i64)])),
+        None if optionals
+            .first()
+            .is_none_or(|eof_error| is_true(state, eof_error)) =>
+        {
+            Err(LispError::Parse("end of file in string".to_string()))
+        }
+        None => {
+            let eof_value = optionals.get(1).cloned().unwrap_or_else(LispValue::nil);
+            let end = LispValue::Int(text.chars().count() as i64);
+            Ok(LispValue::Values(vec![eof_value, end]))
+        }
+    }
+}
+
+/// Writes an object to a string with `:base` and `:radix`, which default to
+/// `*print-base*` and `*print-radix*`. `write-to-string` quotes strings
+/// and `princ-to-string` does not.
+pub fn write_string(
+    state: &State,
+    function: &str,
+    escape: bool,
+    args: Vec<LispValue>,
+) -> LispResult {
+    let (object, keys) = match args.split_first() {
+        Some(argument) => argument,
+        None => return Ok(LispValue::nil()),
+    };
+    let keys = keyword_arguments(keys, &[":base", ":radix"])?;
+
+    let mut options = print_options(state)?;
+    options.escape = escape;
+    if let Some(base) = &keys[0] {
+        options.base = radix_value(function, base)?;
+    }
+    if let Some(radix) = &keys[1] {
+        options.radix = is_true(state, radix);
+    }
+    Ok(LispValue::String(write_to_string(object, options)))
+}
@@ -0,0 +1,282 @@
+use super::types::*;
+use crate::parser::LispValue;
+
+impl Function {
+    /// Docstring of a builtin function. Custom functions and macros keep
+    /// theirs in the state.
+    pub fn documentation(&self) -> Option<&'static str> {
+        let documentation = match self {
+            Function::Add => "Returns the sum of the numbers.",
+            Function::Sub => "Subtracts the other numbers from the first one.",
+            Function::Mul => "Returns the product of the numbers.",
+            Function::Div => "Divides the first number by the other ones, or returns the reciprocal of a single number.",
+            Function::Floor => "Divides a number by the divisor, one by default, rounding toward negative infinity. Returns the quotient and the remainder.",
+            Function::Truncate => "Divides a number by the divisor, one by default, rounding toward zero. Returns the quotient and the remainder.",
+            Function::Round => "Divides a number by the divisor, one by default, rounding to the nearest integer and to even on ties. Returns the quotient and the remainder.",
+            Function::Ceiling => "Divides a number by the divisor, one by default, rounding toward positive infinity. Returns the quotient and the remainder.",
+            Function::Mod => "Returns the remainder of floor division, which has the sign of the divisor.",
+            Function::Rem => "Returns the remainder of truncating division, which has the sign of the number.",
+            Function::OnePlus => "Returns the number plus one.",
+            Function::OneMinus => "Returns the number minus one.",
+            Function::Abs => "Returns the absolute value of a number.",
+            Function::Min => "Returns the least of the numbers.",
+            Function::Max => "Returns the greatest of the numbers.",
+            Function::Expt => "Raises a number to a power. Rationals raised to integers give exact results.",
+            Function::Sqrt => "Returns the square root of a number, which is complex for negative reals.",
+            Function::Isqrt => "Returns the greatest integer whose square is not above a non-negative integer.",
+            Function::Exp => "Returns e raised to a number.",
+            Function::Log => "Returns the natural logarithm of a number, or its logarithm in the given base.",
+            Function::Sin => "Returns the sine of an angle in radians.",
+            Function::Cos => "Returns the cosine of an angle in radians.",
+            Function::Tan => "Returns the tangent of an angle in radians.",
+            Function::Asin => "Returns the arc sine of a number, in radians.",
+            Function::Acos => "Returns the arc cosine of a number, in radians.",
+            Function::Atan => "Returns the arc tangent of a number, or of y/x in the quadrant of the point (x, y), in radians.",
+            Function::Sinh => "Returns the hyperbolic sine of a number.",
+            Function::Cosh => "Returns the hyperbolic cosine of a number.",
+            Function::Tanh => "Returns the hyperbolic tangent of a number.",
+            Function::Asinh => "Returns the hyperbolic arc sine of a number.",
+            Function::Acosh => "Returns the hyperbolic arc cosine of a number.",
+            Function::Atanh => "Returns the hyperbolic arc tangent of a number.",
+            Function::Gcd => "Returns the greatest common divisor of the integers, zero when there are none.",
+            Function::Lcm => "Returns the least common multiple of the integers, one when there are none.",
+            Function::Signum => "Returns -1, 0 or 1 for the sign of a number, as a float for floats.",
+            Function::Zerop => "Returns true when the number is zero.",
+            Function::Plusp => "Returns true when the number is greater than zero.",
+            Function::Minusp => "Returns true when the number is less than zero.",
+            Function::Evenp => "Returns true when the integer is even.",
+            Function::Oddp => "Returns true when the integer is odd.",
+            Function::Numerator => "Returns the numerator of a rational in lowest terms.",
+            Function::Denominator => "Returns the denominator of a rational in lowest terms, which is always positive.",
+            Function::Rational => "Returns the exact rational value of a real.",
+            Function::Rationalize => "Returns the simplest rational that converts back to the same float, or the rational itself.",
+            Function::Complex => "Returns the complex number with the given real and imaginary parts.",
+            Function::Realpart => "Returns the real part of a number.",
+            Function::Imagpart => "Returns the imaginary part of a number.",
+            Function::Conjugate => "Returns the complex conjugate of a number.",
+            Function::Phase => "Returns the angle of a number in radians from the positive real axis.",
+            Function::Logand => "Returns the bitwise and of the integers in two's complement.",
+            Function::Logior => "Returns the bitwise inclusive or of the integers in two's complement.",
+            Function::Logxor => "Returns the bitwise exclusive or of the integers in two's complement.",
+            Function::Lognot => "Returns the bitwise complement of an integer in two's complement.",
+            Function::Logtest => "Returns true when the two integers have a one bit in common.",
+            Function::Ash => "Shifts an integer left by a positive count of bits, or right by a negative count.",
+            Function::Logbitp => "Returns true when the bit at the index of the integer is set.",
+            Function::Logcount => "Returns the number of one bits of a non-negative integer, or of zero bits of a negative one.",
+            Function::IntegerLength => "Returns the number of bits needed for an integer in two's complement, without the sign.",
+            Function::Byte => "Returns the byte specifier for a size in bits and a bit position.",
+            Function::Ldb => "Returns the bits of an integer selected by a byte specifier.",
+            Function::Dpb => "Returns an integer with the bits selected by a byte specifier replaced by a new byte.",
+            Function::Random => "Returns a random number from zero up to but not including a positive integer or float, using *random-state* or the given state.",
+            Function::ParseInteger => "Reads an integer from a string with :radix, :start, :end and :junk-allowed, returning it and the index where reading stopped.",
+            Function::ReadFromString => "Reads the first form of a string in *read-base*, returning it and the index after it.",
+            Function::WriteToString => "Returns the printed representation of an object with strings quoted, using :base and :radix or *print-base* and *print-radix*.",
+            Function::PrincToString => "Returns the printed representation of an object with strings unquoted, using :base and :radix or *print-base* and *print-radix*.",
+            Function::Float => "Converts a real to a float in the format of the optional prototype float. Floats keep their format and rationals become double floats.",
+            Function::Coerce => "Converts a real to the float type float, single-float, double-float, short-float or long-float. Any object is of type t.",
+            Function::DecodeFloat => "Returns the significand of a float between 0.5 and 1, its exponent as a power of two, and its sign as a float.",
+            Function::MakeRandomState => "Returns a copy of *random-state* for nil, a copy of a random state, a state seeded from the clock for t, or a state seeded with a non-negative integer.",
+            Function::Println => "Prints the values followed by a newline.",
+            Function::Defvar => "Defines a special variable, assigning the value only if it is unbound.",
+            Function::Defparameter => "Defines a special variable and always assigns the value.",
+            Function::Defconstant => "Defines a constant, which can not be assigned or bound afterwards.",
+            Function::Defun => "Defines a global function.",
+            Function::Setq => "Assigns values to variables and returns the last value.",
+            Function::Let => "Binds variables to values computed before any binding, then runs the body.",
+            Function::LetStar => "Binds variables one after another, then runs the body.",
+            Function::If => "Evaluates the then form when the test is true, and the else form otherwise.",
+            Function::Progn => "Evaluates the forms in order and returns the value of the last one.",
+            Function::Dotimes => "Runs the body with the variable bound to each integer from 0 below the count.",
+            Function::Dolist => "Runs the body with the variable bound to each element of the list.",
+            Function::Do => "Iterates with variables stepped in parallel until the end test is true.",
+            Function::DoStar => "Iterates with variables stepped in sequence until the end test is true.",
+            Function::Loop => "Runs the body forever, until a return.",
+            Function::While => "Runs the body as long as the test is true.",
+            Function::Return => "Ends the innermost loop with the values of the form.",
+            Function::Cons => "Returns a new cons with the given car and cdr.",
+            Function::Car => "Returns the first element of a list.",
+            Function::Cdr => "Returns the list without its first element.",
+            Function::First => "Returns the first element of a list.",
+            Function::Rest => "Returns the list without its first element.",
+            Function::List => "Returns a list of the arguments.",
+            Function::ListStar => "Returns a list of the arguments, with the last one as its tail.",
+            Function::Append => "Returns the concatenation of the lists.",
+            Function::Length => "Returns the number of elements of a sequence.",
+            Function::Nth => "Returns the element of the list at the index.",
+            Function::Nthcdr => "Returns the list without its first n elements.",
+            Function::Last => "Returns the last n conses of a list, one by default.",
+            Function::Butlast => "Returns the list without its last n elements, one by default.",
+            Function::Reverse => "Returns a list with the elements in reverse order.",
+            Function::Nreverse => "Returns a list with the elements in reverse order.",
+            Function::Member => "Returns the tail of the list starting with the item, or nil.",
+            Function::CopyList => "Returns a copy of the list.",
+            Function::Null => "Returns true when the value is nil.",
+            Function::Endp => "Returns true when the list is empty.",
+            Function::Equals => "Returns true when all the values are equal.",
+            Function::NotEquals => "Returns true when no two values are equal.",
+            Function::Grater => "Returns true when the numbers are in decreasing order.",
+            Function::GraterOrEquals => "Returns true when the numbers are in non-increasing order.",
+            Function::Less => "Returns true when the numbers are in increasing order.",
+            Function::LessOrEquals => "Returns true when the numbers are in non-decreasing order.",
+            Function::Or => "Returns the first true value, without evaluating the forms after it.",
+            Function::And => "Returns the first false value or the last value, without evaluating the forms after a false one.",
+            Function::Not => "Returns true when the value is false.",
+            Function::Lambda => "Returns an anonymous function.",
+            Function::SharpQuote => "Returns the function with the given name, or the function of a lambda expression.",
+            Function::Funcall => "Calls a function with the arguments.",
+            Function::Apply => "Calls a function with the arguments, the last of which is a list of further arguments.",
+            Function::Defmacro => "Defines a macro.",
+            Function::Macroexpand1 => "Expands a macro call once.",
+            Function::Macroexpand => "Expands a form until it is no longer a macro call.",
+            Function::Gensym => "Returns a fresh uninterned symbol.",
+            Function::Quote => "Returns the form without evaluating it.",
+            Function::Eval => "Evaluates a form in the global environment.",
+            Function::Values => "Returns the arguments as multiple values.",
+            Function::MultipleValueBind => "Binds variables to the values of a form, then runs the body.",
+            Function::MultipleValueList => "Returns a list of the values of a form.",
+            Function::MultipleValueCall => "Calls a function with all the values of the forms as arguments.",
+            Function::NthValue => "Returns the nth value of a form.",
+            Function::Documentation => "Returns the docstring of a function or variable, or nil.",
+            Function::Describe => "Prints a description of a symbol.",
+            Function::Custom(..) | Function::Macro(..) => return None,
+        };
+        Some(documentation)
+    }
+
+    /// Tells whether the function receives its arguments unevaluated, as
+    /// special forms and macros do.
+    pub fn is_special_form(&self) -> bool {
+        matches!(
+            self,
+            Function::Defvar
+                | Function::Defparameter
+                | Function::Defconstant
+                | Function::Defun
+                | Function::Defmacro
+                | Function::Setq
+                | Function::Let
+                | Function::LetStar
+                | Function::If
+                | Function::Progn
+                | Function::Dotimes
+                | Function::Dolist
+                | Function::Do
+                | Function::DoStar
+                | Function::Loop
+                | Function::While
+                | Function::Or
+                | Function::And
+                | Function::Lambda
+                | Function::SharpQuote
+                | Function::Quote
+                | Function::MultipleValueBind
+                | Function::MultipleValueList
+                | Function::MultipleValueCall
+                | Function::NthValue
+                | Function::Return
+                | Function::Macro(..)
+        )
+    }
+}
+
+/// Takes the docstring off the front of a body. A string that is the only
+/// form is the value of the body instead.
+pub fn read_docstring(body: &mut Vec<LispValue>) -> Option<String> {
+    match body.first() {
+        Some(LispValue::String(_)) if body.len() > 1 => match body.remove(0) {
+            LispValue::String(documentation) => Some(documentation),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn read_doc_type(value: &LispValue) -> Option<DocType> {
+    match value {
+        LispValue::Name(name) if name == "function" => Some(DocType::Function),
+        LispValue::Name(name) if name == "variable" => Some(DocType::Variable),
+        _ => None,
+    }
+}
+
+fn function_documentation(state: &State, name: &str) -> Option<String> {
+    match state.get_documentation(name, DocType::Function) {
+        Some(documentation) => Some(documentation),
+        None => state
+            .get_function(name)?
+            .documentation()
+            .map(str::to_string),
+    }
+}
+
+pub fn documentation(state: &State, args: Vec<LispValue>) -> LispResult {
+    let (name, doc_type) = match args.as_slice() {
+        [LispValue::Name(name), doc_type] => match read_doc_type(doc_type) {
+            Some(doc_type) => (name, doc_type),
+            None => return Ok(LispValue::nil()),
+        },
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let documentation = match doc_type {
+        DocType::Function => function_documentation(state, name),
+        DocType::Variable => state.get_documentation(name, DocType::Variable),
+    };
+    Ok(documentation.map_or_else(LispValue::nil, LispValue::String))
+}
+
+/// Describes what a symbol names: its variable and its function, with their
+/// docstrings.
+pub fn describe_symbol(state: &State, name: &str) -> String {
+    let mut lines = Vec::new();
+
+    let variable = match state.get_constant(name) {
+        Some(value) => Some(("a constant", value)),
+        None if state.is_special(name) => state
+            .get_value(name)
+            .map(|value| ("a special variable", value)),
+        None => state
+            .global()
+            .get_value(name)
+            .map(|value| ("a variable", value)),
+    };
+    if let Some((kind, value)) = variable {
+        lines.push(format!("{} names {}:", name, kind));
+        lines.push(format!("  Value: {}", to_print_string(&value)));
+        if let Some(documentation) = state.get_documentation(name, DocType::Variable) {
+            lines.push(format!("  Documentation: {}", documentation));
+        }
+    }
+
+    if let Some(function) = state.get_function(name) {
+        let kind = match &function {
+            Function::Macro(..) => "a macro",
+            Function::Custom(..) => "a function",
+            function if function.is_special_form() => "a special operator",
+            _ => "a builtin function",
+        };
+        lines.push(format!("{} names {}:", name, kind));
+        if let Some(documentation) = function_documentation(state, name) {
+            lines.push(format!("  Documentation: {}", documentation));
+        }
+    }
+
+    if lines.is_empty() {
+        lines.push(format!("{} is a symbol", name));
+    }
+    lines.join("\n")
+}
+
+pub fn describe(state: &State, args: Vec<LispValue>) -> LispResult {
+    match args.as_slice() {
+        [LispValue::Name(name)] => {
+            println!("{}", describe_symbol(state, name));
+            Ok(LispValue::Values(Vec::new()))
+        }
+        [value] => {
+            println!("{}", to_print_string(value));
+            Ok(LispValue::Values(Vec::new()))
+        }
+        _ => Ok(LispValue::nil()),
+    }
+}
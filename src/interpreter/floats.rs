@@ -0,0 +1,278 @@
+use super::basic::*;
+use super::numbers::real_contagion;
+use super::types::*;
+use crate::parser::LispValue;
+
+/// The float formats. Short floats are single floats and long floats are
+/// double floats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatFormat {
+    Single,
+    Double,
+}
+
+impl FloatFormat {
+    /// Reads a float format from the name of its type, like `single-float`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "single-float" | "short-float" => Some(FloatFormat::Single),
+            "double-float" | "long-float" => Some(FloatFormat::Double),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FloatFormat::Single => "single-float",
+            FloatFormat::Double => "double-float",
+        }
+    }
+
+    /// The format of a float, or None for other values.
+    pub fn of(value: &LispValue) -> Option<Self> {
+        match value {
+            LispValue::SingleFloat(_) => Some(FloatFormat::Single),
+            LispValue::Float(_) => Some(FloatFormat::Double),
+            _ => None,
+        }
+    }
+
+    /// The exponent marker that names the format when it is not the default.
+    fn marker(self) -> char {
+        match self {
+            FloatFormat::Single => 'f',
+            FloatFormat::Double => 'd',
+        }
+    }
+
+    /// A float of this format, rounding single floats to nearest.
+    pub fn float(self, value: f64) -> LispValue {
+        match self {
+            FloatFormat::Single => LispValue::SingleFloat(value as f32),
+            FloatFormat::Double => LispValue::Float(value),
+        }
+    }
+}
+
+/// The format of a float computed from the arguments: single when every
+/// float among them is single, otherwise double. Rationals alone give double
+/// floats, the default format.
+pub fn result_format(args: &[LispValue]) -> FloatFormat {
+    let single = args
+        .iter()
+        .any(|arg| matches!(arg, LispValue::SingleFloat(_)));
+    let double = args.iter().any(|arg| matches!(arg, LispValue::Float(_)));
+    match single && !double {
+        true => FloatFormat::Single,
+        false => FloatFormat::Double,
+    }
+}
+
+/// A float computed from the arguments, in the format given by
+/// `result_format`.
+pub fn float_result(args: &[LispValue], value: f64) -> LispValue {
+    result_format(args).float(value)
+}
+
+/// Writes a float with the shortest digits that read back as the same float.
+/// Magnitudes from 10^-3 up to 10^7 are written without an exponent, others
+/// as one digit, a fraction and an exponent. Floats not of the `default`
+/// format get the exponent marker of their format.
+pub fn format_float(value: f64, format: FloatFormat, default: FloatFormat) -> String {
+    if value.is_nan() {
+        return format!("#<{} quiet NaN>", format.name());
+    }
+    if value.is_infinite() {
+        let sign = if value > 0.0 { "positive" } else { "negative" };
+        return format!("#.{}-{}-infinity", format.name(), sign);
+    }
+
+    let scientific = match format {
+        FloatFormat::Single => format!("{:e}", value as f32),
+        FloatFormat::Double => format!("{:e}", value),
+    };
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i64 = exponent.parse().unwrap_or(0);
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let marker = match format == default {
+        true => None,
+        false => Some(format.marker()),
+    };
+
+    let magnitude = value.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        let point = exponent + 1;
+        let text = if point <= 0 {
+            format!("0.{}{}", "0".repeat(-point as usize), digits)
+        } else if point as usize >= digits.len() {
+            format!("{}{}.0", digits, "0".repeat(point as usize - digits.len()))
+        } else {
+            format!(
+                "{}.{}",
+                &digits[..point as usize],
+                &digits[point as usize..]
+            )
+        };
+        return match marker {
+            Some(marker) => format!("{}{}{}0", sign, text, marker),
+            None => format!("{}{}", sign, text),
+        };
+    }
+
+    let fraction = match &digits[1..] {
+        "" => "0",
+        fraction => fraction,
+    };
+    format!(
+        "{}{}.{}{}{}",
+        sign,
+        &digits[..1],
+        fraction,
+        marker.unwrap_or('e'),
+        exponent
+    )
+}
+
+/// Splits a finite float into a significand in [0.5, 1) with the sign of
+/// the float and a power of two. Zero has the exponent zero.
+fn frexp(value: f64) -> (f64, i64) {
+    if value == 0.0 || !value.is_finite() {
+        return (value, 0);
+    }
+
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64;
+    if exponent == 0 {
+        // Subnormal floats are scaled into the normal range first.
+        let (significand, exponent) = frexp(value * 2f64.powi(54));
+        return (significand, exponent - 54);
+    }
+    let significand = f64::from_bits((bits & !(0x7ff << 52)) | (1022 << 52));
+    (significand, exponent - 1022)
+}
+
+/// Converts a real to a float in the format of a prototype float, or keeps
+/// the format of a float. Rationals become double floats.
+pub fn float(args: Vec<LispValue>) -> LispResult {
+    let (number, format) = match args.as_slice() {
+        [number] => (
+            number,
+            FloatFormat::of(number).unwrap_or(FloatFormat::Double),
+        ),
+        [number, prototype] => match FloatFormat::of(prototype) {
+            Some(format) => (number, format),
+            None => {
+                return Err(LispError::ArgumentType {
+                    function: "float".to_string(),
+                    position: 2,
+                    expected: "float".to_string(),
+                    value: prototype.clone(),
+                })
+            }
+        },
+        _ => return Ok(LispValue::nil()),
+    };
+
+    real_contagion("float", &args[..1])?;
+    Ok(format.float(to_float(number)))
+}
+
+/// Converts a value to a type. Only conversions of reals to float types are
+/// supported, and values of type `t` are returned as they are.
+pub fn coerce(args: Vec<LispValue>) -> LispResult {
+    let (value, type_name) = match args.as_slice() {
+        [value, LispValue::Name(type_name)] => (value, type_name.as_str()),
+        _ => return Ok(LispValue::nil()),
+    };
+
+    match type_name {
+        "t" => Ok(value.clone()),
+        "float" => float(vec![value.clone()]),
+        name => match FloatFormat::from_name(name) {
+            Some(format) => {
+                real_contagion("coerce", &args[..1])?;
+                Ok(format.float(to_float(value)))
+            }
+            None => Err(type_error("coerce", name, value.clone())),
+        },
+    }
+}
+
+/// Returns the significand of a float in [0.5, 1), its exponent as a power
+/// of two, and its sign as a float of the same format.
+pub fn decode_float(args: Vec<LispValue>) -> LispResult {
+    let (value, format) = match args.as_slice() {
+        [value] => match FloatFormat::of(value) {
+            Some(format) => (to_float(value), format),
+            None => {
+                return Err(LispError::ArgumentType {
+                    function: "decode-float".to_string(),
+                    position: 1,
+                    expected: "float".to_string(),
+                    value: value.clone(),
+                })
+            }
+        },
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let (significand, exponent) = frexp(value.abs());
+    let sign = if value.is_sign_negative() { -1.0 } else { 1.0 };
+    Ok(LispValue::Values(vec![
+        format.float(significand),
+        LispValue::Int(exponent),
+        format.float(sign),
+    ]))
+}
+
+#[cfg(test)]
+mod floats_test {
+    use super::*;
+
+    fn double(value: f64) -> String {
+        format_float(value, FloatFormat::Double, FloatFormat::Double)
+    }
+
+    #[test]
+    fn printing() {
+        assert_eq!(double(1.0), "1.0");
+        assert_eq!(double(123.456), "123.456");
+        assert_eq!(double(-0.0), "-0.0");
+        assert_eq!(double(0.001), "0.001");
+        assert_eq!(double(1234567.5), "1234567.5");
+        assert_eq!(double(1e7), "1.0e7");
+        assert_eq!(double(1e20), "1.0e20");
+        assert_eq!(double(-1.5e-7), "-1.5e-7");
+        assert_eq!(double(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(double(f64::INFINITY), "#.double-float-positive-infinity");
+        assert_eq!(
+            format_float(0.1, FloatFormat::Single, FloatFormat::Double),
+            "0.1f0"
+        );
+        assert_eq!(
+            format_float(1e20, FloatFormat::Single, FloatFormat::Double),
+            "1.0f20"
+        );
+        assert_eq!(
+            format_float(2.5, FloatFormat::Double, FloatFormat::Single),
+            "2.5d0"
+        );
+        assert_eq!(
+            format_float(2.5, FloatFormat::Single, FloatFormat::Single),
+            "2.5"
+        );
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(frexp(1.0), (0.5, 1));
+        assert_eq!(frexp(0.75), (0.75, 0));
+        assert_eq!(frexp(-8.0), (-0.5, 4));
+        assert_eq!(frexp(f64::MIN_POSITIVE / 4.0), (0.5, -1023));
+        assert_eq!(frexp(0.0), (0.0, 0));
+    }
+}
@@ -1,354 +1,963 @@
-use super::basic::*;
+use super::bignum::BigInt;
+use super::bits::*;
+use super::complex::*;
+use super::conversion::*;
+use super::documentation::*;
+use super::floats::*;
+use super::lambda_list::*;
+use super::lists::*;
+use super::macros::*;
+use super::numbers::*;
+use super::random::*;
+use super::ratio::Ratio;
 use super::types::*;
+use super::values::*;
 use crate::parser::LispValue;
+use std::cmp::Ordering;
+
+/// Prints the values followed by a newline, written as by `princ-to-string`
+/// with the current printer variables.
+fn println(state: &State, args: Vec<LispValue>) -> LispResult {
+    let options = print_options(state)?;
+    let text: String = args
+        .iter()
+        .map(|value| write_to_string(value, options))
+        .collect();
+    println!("{}", text.replace("\\n", "\n"));
+    Ok(LispValue::nil())
+}
 
-fn add(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
-    match unified_type {
-        Type::Int => Some(LispValue::Int(perform_add(&convert_to_int_array(args)))),
-        Type::Float => Some(LispValue::Float(perform_add(&convert_to_float_array(args)))),
-        _ => None,
+fn create_custom_function(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() < 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let name;
+    if let LispValue::Name(n) = args.remove(0) {
+        name = n;
+    } else {
+        return Ok(LispValue::nil());
     }
+
+    let f_args = match read_lambda_list(args.remove(0), false) {
+        Some(parameters) => parameters,
+        None => return Ok(LispValue::nil()),
+    };
+
+    let documentation = read_docstring(&mut args);
+    state.set_documentation(&name, DocType::Function, documentation);
+
+    let mut functions = Vec::new();
+
+    for f in args.drain(..) {
+        functions.push(f);
+    }
+
+    let environment = state.clone();
+    state.add_function(
+        name.clone(),
+        Function::Custom(Box::new(f_args), functions, environment),
+    );
+    Ok(LispValue::Name(name))
 }
 
-fn sub(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
-    match unified_type {
-        Type::Int => Some(LispValue::Int(perform_sub(&convert_to_int_array(args)))),
-        Type::Float => Some(LispValue::Float(perform_sub(&convert_to_float_array(args)))),
-        _ => None,
+pub fn create_lambda(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
+    if args.is_empty() {
+        return None;
     }
+
+    let parameters = read_lambda_list(args.remove(0), false)?;
+    let function = Function::Custom(Box::new(parameters), args, state.clone());
+    Some(LispValue::Lambda(Box::new(function)))
 }
 
-fn mul(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
-    match unified_type {
-        Type::Int => Some(LispValue::Int(perform_mul(&convert_to_int_array(args)))),
-        Type::Float => Some(LispValue::Float(perform_mul(&convert_to_float_array(args)))),
-        _ => None,
+fn function_ref(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    match args.remove(0) {
+        LispValue::Name(name) => match state.get_function(name.as_str()) {
+            Some(function) if !function.is_special_form() => {
+                Ok(LispValue::Lambda(Box::new(function)))
+            }
+            _ => Err(LispError::UndefinedFunction(name)),
+        },
+        LispValue::Function(mut lambda) => {
+            if lambda.is_empty() || lambda.remove(0) != LispValue::Name("lambda".to_string()) {
+                return Ok(LispValue::nil());
+            }
+            Ok(create_lambda(state, lambda).unwrap_or_else(LispValue::nil))
+        }
+        _ => Ok(LispValue::nil()),
     }
 }
 
-fn div(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    replace_variables_with_values(state, &mut args);
-    execute_functions(state, &mut args);
+fn quote(mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 1, Some(1))?;
+    Ok(args.remove(0))
+}
 
-    let unified_type = get_args_unified_type(&args);
-    match unified_type {
-        Type::Int => Some(LispValue::Int(perform_div(&convert_to_int_array(args)))),
-        Type::Float => Some(LispValue::Float(perform_div(&convert_to_float_array(args)))),
-        _ => None,
+/// Evaluates a form in the global environment, so it does not see the local
+/// variables of the caller.
+fn eval(state: &State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
     }
+    evaluate_values(&mut state.global(), args.remove(0))
 }
 
-#[allow(dead_code)]
-fn print(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
+fn funcall(state: &mut State, args: Vec<LispValue>) -> LispResult {
+    let (function, args) = designated_call(state, Function::Funcall, args)?;
+    call(state, function, args)
+}
+
+fn apply(state: &mut State, args: Vec<LispValue>) -> LispResult {
+    let (function, args) = designated_call(state, Function::Apply, args)?;
+    call(state, function, args)
+}
 
-    let unified_type = get_args_unified_type(&args);
-    match unified_type {
-        Type::Int => perform_print(&convert_to_int_array(args)),
-        Type::Float => perform_print(&convert_to_float_array(args)),
-        Type::Boolean => perform_print(&convert_to_boolean_array(args)),
-        Type::String => perform_print_string(&convert_to_string_array(args)),
-        _ => {}
+/// Finds the function and the arguments of a call through `funcall` or
+/// `apply`. Names designate the functions they name, which must not be
+/// macros or special forms.
+pub fn designated_call(
+    state: &State,
+    caller: Function,
+    mut args: Vec<LispValue>,
+) -> Result<(Function, Vec<LispValue>), LispError> {
+    let name = match caller {
+        Function::Apply => "apply",
+        _ => "funcall",
     };
+    if let Function::Apply = caller {
+        check_argument_count(&args, 2, None)?;
+        match args.pop().unwrap_or_else(LispValue::nil) {
+            LispValue::Function(list) => args.extend(list),
+            value => return Err(type_error(name, "list", value)),
+        }
+    }
+    check_argument_count(&args, 1, None)?;
 
-    None
+    let function = function_designator(state, name, args.remove(0))?;
+    Ok((function, args))
 }
 
-fn println(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
+/// The function a function designator names: a function, or a symbol naming
+/// a global function that is not a special form or macro.
+pub fn function_designator(
+    state: &State,
+    caller: &str,
+    designator: LispValue,
+) -> Result<Function, LispError> {
+    match designator {
+        LispValue::Lambda(function) => Ok(*function),
+        LispValue::Name(function_name) => match state.get_function(function_name.as_str()) {
+            Some(function) if !function.is_special_form() => Ok(function),
+            _ => Err(LispError::UndefinedFunction(function_name)),
+        },
+        value => Err(type_error(caller, "function", value)),
+    }
+}
 
-    let unified_type = get_args_unified_type(&args);
-    match unified_type {
-        Type::Int => perform_println(&convert_to_int_array(args)),
-        Type::Float => perform_println(&convert_to_float_array(args)),
-        Type::Boolean => perform_println(&convert_to_boolean_array(args)),
-        Type::String => perform_println_string(&convert_to_string_array(args)),
-        _ => {}
+/// Runs a custom function. Calls in tail position of its body are not
+/// executed recursively but replace the current call, so tail recursive
+/// functions run in constant stack space.
+fn execute_custom_function(
+    state: &mut State,
+    args: Vec<LispValue>,
+    parameters: Box<LambdaList>,
+    code: Vec<LispValue>,
+    environment: State,
+) -> LispResult {
+    state.enter_call()?;
+    let result = run_custom_function(args, parameters, code, environment);
+    state.leave_call();
+    result
+}
+
+fn run_custom_function(
+    mut args: Vec<LispValue>,
+    mut parameters: Box<LambdaList>,
+    mut code: Vec<LispValue>,
+    mut environment: State,
+) -> LispResult {
+    loop {
+        let mut local_state = environment.create_child();
+        if let Err(error) = bind_arguments(&mut local_state, &parameters, args) {
+            local_state.restore_specials();
+            return Err(error);
+        }
+
+        match scope_body(&mut local_state, code)? {
+            Tail::Value(result) => return Ok(result),
+            Tail::Call(
+                Function::Custom(next_parameters, next_code, next_environment),
+                next_args,
+            ) => {
+                args = next_args;
+                parameters = next_parameters;
+                code = next_code;
+                environment = next_environment;
+            }
+            Tail::Call(function, args) => return call(&mut local_state, function, args),
+        }
+    }
+}
+
+/// Evaluates a list of forms, leaving the last one in tail position.
+pub fn evaluate_body(state: &mut State, mut body: Vec<LispValue>) -> Result<Tail, LispError> {
+    let last = match body.pop() {
+        Some(last) => last,
+        None => return Ok(Tail::Value(LispValue::nil())),
     };
 
-    None
+    for element in body {
+        evaluate(state, element)?;
+    }
+    evaluate_tail(state, last)
+}
+
+/// Runs `run` and then ends the dynamic bindings made in `state`, whether or
+/// not it failed.
+pub fn scoped<T>(
+    state: &mut State,
+    run: impl FnOnce(&mut State) -> Result<T, LispError>,
+) -> Result<T, LispError> {
+    let result = run(state);
+    state.restore_specials();
+    result
 }
 
-fn create_custom_function(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    if args.len() < 3 {
-        return None;
+/// Evaluates the body of a new scope. When the scope bound special variables
+/// the body is finished here, so they are restored only after its last call.
+pub fn scope_body(state: &mut State, body: Vec<LispValue>) -> Result<Tail, LispError> {
+    if !state.has_special_bindings() {
+        return evaluate_body(state, body);
     }
 
-    let name;
-    if let LispValue::Name(n) = args.remove(0) {
-        name = n;
+    scoped(state, |state| {
+        let tail = evaluate_body(state, body)?;
+        finish_tail(state, tail).map(Tail::Value)
+    })
+}
+
+pub fn progn(state: &mut State, args: Vec<LispValue>) -> Result<Tail, LispError> {
+    evaluate_body(state, args)
+}
+
+pub fn if_block(state: &mut State, mut args: Vec<LispValue>) -> Result<Tail, LispError> {
+    check_argument_count(&args, 2, Some(3))?;
+
+    let condition = evaluate(state, args.remove(0))?;
+    if is_true(state, &condition) {
+        evaluate_tail(state, args.remove(0))
+    } else if args.len() == 2 {
+        evaluate_tail(state, args.remove(1))
     } else {
-        return None;
+        Ok(Tail::Value(LispValue::nil()))
+    }
+}
+
+/// Defines a global special variable. `defvar` only evaluates and assigns
+/// its value when the variable is unbound, `defparameter` always assigns it.
+fn define_var(state: &mut State, mut args: Vec<LispValue>, overwrite: bool) -> LispResult {
+    let required = if overwrite { 2 } else { 1 };
+    if args.len() < required || args.len() > 3 {
+        return Ok(LispValue::nil());
+    }
+
+    let name = match args.remove(0) {
+        LispValue::Name(name) => name,
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let documentation = match variable_docstring(&mut args) {
+        Some(documentation) => documentation,
+        None => return Ok(LispValue::nil()),
+    };
+
+    let value = match args.pop() {
+        Some(value) if overwrite || state.global().get_value(&name).is_none() => {
+            Some(evaluate(state, value)?)
+        }
+        _ => None,
+    };
+    state.define_special(name.clone(), value, overwrite)?;
+    if documentation.is_some() {
+        state.set_documentation(&name, DocType::Variable, documentation);
+    }
+    Ok(LispValue::Name(name))
+}
+
+/// Takes the docstring after the value of a variable definition. Returns
+/// None when the third argument is not a string.
+fn variable_docstring(args: &mut Vec<LispValue>) -> Option<Option<String>> {
+    if args.len() < 2 {
+        return Some(None);
+    }
+
+    match args.pop() {
+        Some(LispValue::String(documentation)) => Some(Some(documentation)),
+        _ => None,
+    }
+}
+
+/// Defines a constant, which can not be assigned or bound afterwards.
+fn define_constant(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() < 2 || args.len() > 3 {
+        return Ok(LispValue::nil());
     }
 
-    let mut f_args = Vec::new();
-    if let LispValue::Function(args) = args.remove(0) {
-        for arg in args {
-            if let LispValue::Name(n) = arg {
-                f_args.push(n);
+    let name = match args.remove(0) {
+        LispValue::Name(name) => name,
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let documentation = match variable_docstring(&mut args) {
+        Some(documentation) => documentation,
+        None => return Ok(LispValue::nil()),
+    };
+
+    let value = evaluate(state, args.remove(0))?;
+    state.define_constant(name.clone(), value)?;
+    if documentation.is_some() {
+        state.set_documentation(&name, DocType::Variable, documentation);
+    }
+    Ok(LispValue::Name(name))
+}
+
+fn setq(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if !args.len().is_multiple_of(2) {
+        return Err(LispError::ArgumentCount {
+            expected: "an even number of arguments".to_string(),
+            given: args.len(),
+        });
+    }
+
+    let mut result = LispValue::nil();
+    while !args.is_empty() {
+        let name = match args.remove(0) {
+            LispValue::Name(name) => name,
+            value => return Err(type_error("setq", "symbol", value)),
+        };
+        let value = evaluate(state, args.remove(0))?;
+        state.set_variable(name, value.clone())?;
+        result = value;
+    }
+    Ok(result)
+}
+
+type Binding = Option<(String, LispValue)>;
+
+fn read_binding(state: &mut State, binding: LispValue) -> Result<Binding, LispError> {
+    match binding {
+        LispValue::Name(name) => Ok(Some((name, LispValue::nil()))),
+        LispValue::Function(mut pair) => {
+            if pair.is_empty() || pair.len() > 2 {
+                return Ok(None);
             }
+
+            if let LispValue::Name(name) = pair.remove(0) {
+                let value = match pair.pop() {
+                    Some(value) => evaluate(state, value)?,
+                    None => LispValue::nil(),
+                };
+                return Ok(Some((name, value)));
+            }
+            Ok(None)
         }
-    } else {
-        return None;
+        _ => Ok(None),
     }
+}
 
-    let mut functions = Vec::new();
+/// `let` evaluates every binding in the outer state before binding any of
+/// them, `let*` binds them one after another.
+fn bind_let(
+    state: &mut State,
+    local_state: &mut State,
+    bindings: Vec<LispValue>,
+    sequential: bool,
+) -> Result<bool, LispError> {
+    let mut values = Vec::new();
+    for binding in bindings {
+        let binding = if sequential {
+            read_binding(local_state, binding)?
+        } else {
+            read_binding(state, binding)?
+        };
 
-    for f in args.drain(..) {
-        functions.push(f);
+        match binding {
+            Some((name, value)) if sequential => local_state.add_variable(name, value)?,
+            Some(binding) => values.push(binding),
+            None => return Ok(false),
+        }
     }
 
-    state.add_function(name, Function::Custom(f_args, functions));
-    None
+    for (name, value) in values {
+        local_state.add_variable(name, value)?;
+    }
+    Ok(true)
 }
 
-fn execute_custom_function(
+pub fn let_block(
     state: &mut State,
     mut args: Vec<LispValue>,
-    parameters: Vec<String>,
-    code: Vec<LispValue>,
-) -> Option<LispValue> {
-    if args.len() != parameters.len() {
+    sequential: bool,
+) -> Result<Tail, LispError> {
+    if args.is_empty() {
+        return Ok(Tail::Value(LispValue::nil()));
+    }
+
+    let bindings = match args.remove(0) {
+        LispValue::Function(bindings) => bindings,
+        _ => return Ok(Tail::Value(LispValue::nil())),
+    };
+
+    let mut local_state = state.create_child();
+    match bind_let(state, &mut local_state, bindings, sequential) {
+        Ok(true) => scope_body(&mut local_state, args),
+        bound => {
+            local_state.restore_specials();
+            bound.map(|_| Tail::Value(LispValue::nil()))
+        }
+    }
+}
+
+/// Loops are an implicit block, a `return` inside them ends the loop with
+/// the returned value. The block is lexical: functions called from the loop
+/// cannot end it, but closures made in its body can.
+fn loop_block(state: &State, run: impl FnOnce(&mut State) -> LispResult) -> LispResult {
+    let (mut block_state, block) = state.create_block();
+    match run(&mut block_state) {
+        Err(LispError::Return(returned, value)) if returned == block => Ok(value),
+        result => result,
+    }
+}
+
+fn evaluate_iteration(
+    state: &State,
+    name: &str,
+    value: LispValue,
+    body: &[LispValue],
+) -> Result<(), LispError> {
+    let mut local_state = state.create_child();
+    scoped(&mut local_state, |local_state| {
+        local_state.add_variable(name.to_string(), value)?;
+        for element in body {
+            evaluate(local_state, element.clone())?;
+        }
+        Ok(())
+    })
+}
+
+fn read_loop_spec(args: &mut Vec<LispValue>) -> Option<(String, LispValue, Option<LispValue>)> {
+    if args.is_empty() {
         return None;
     }
 
-    let mut local_state = State::create_child(state);
-    for param in parameters {
-        local_state.add_variable(param.to_string(), args.remove(0));
+    let mut spec = match args.remove(0) {
+        LispValue::Function(spec) if spec.len() == 2 || spec.len() == 3 => spec,
+        _ => return None,
+    };
+
+    let result = if spec.len() == 3 { spec.pop() } else { None };
+    let value = spec.pop()?;
+    match spec.pop() {
+        Some(LispValue::Name(name)) => Some((name, value, result)),
+        _ => None,
     }
+}
 
-    let mut result = None;
-    for element in code {
-        if let LispValue::Function(mut arguments) = element {
-            if let LispValue::Name(name) = arguments.remove(0) {
-                result = execute_function(&mut local_state, name.as_str(), arguments);
-            }
+fn loop_result(
+    state: &State,
+    name: String,
+    value: LispValue,
+    result: Option<LispValue>,
+) -> LispResult {
+    match result {
+        Some(result) => {
+            let mut local_state = state.create_child();
+            scoped(&mut local_state, |local_state| {
+                local_state.add_variable(name, value)?;
+                evaluate_values(local_state, result)
+            })
         }
+        None => Ok(LispValue::nil()),
     }
+}
 
-    result
+fn dotimes(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    let (name, count, result) = match read_loop_spec(&mut args) {
+        Some(spec) => spec,
+        None => return Ok(LispValue::nil()),
+    };
+
+    let count = match evaluate(state, count)? {
+        LispValue::Int(count) => LispValue::Int(count.max(0)),
+        LispValue::BigInt(count) if count.is_negative() => LispValue::Int(0),
+        LispValue::BigInt(count) => LispValue::BigInt(count),
+        value => return Err(type_error("dotimes", "integer", value)),
+    };
+
+    // Counts beyond an `i64` keep going with bignums once the small ones run
+    // out.
+    let limit = match count {
+        LispValue::Int(count) => count,
+        _ => i64::MAX,
+    };
+    for i in 0..limit {
+        evaluate_iteration(state, &name, LispValue::Int(i), &args)?;
+    }
+    if let LispValue::BigInt(count) = &count {
+        let mut i = BigInt::from(i64::MAX);
+        while &i < count {
+            evaluate_iteration(state, &name, LispValue::integer(i.clone()), &args)?;
+            i = i.add(&BigInt::from(1));
+        }
+    }
+    loop_result(state, name, count, result)
 }
 
-fn define_var(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    if args.len() != 2 {
-        return None;
+fn dolist(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    let (name, list, result) = match read_loop_spec(&mut args) {
+        Some(spec) => spec,
+        None => return Ok(LispValue::nil()),
+    };
+
+    let list = match evaluate(state, list)? {
+        LispValue::Function(list) => list,
+        value => return Err(type_error("dolist", "list", value)),
+    };
+
+    for value in list {
+        evaluate_iteration(state, &name, value, &args)?;
     }
+    loop_result(state, name, LispValue::nil(), result)
+}
 
-    if let LispValue::Name(name) = args.remove(0) {
-        let value = args.remove(0);
+type LoopVariable = (String, Option<LispValue>, Option<LispValue>);
+
+fn read_loop_variable(variable: LispValue) -> Option<LoopVariable> {
+    match variable {
+        LispValue::Name(name) => Some((name, None, None)),
+        LispValue::Function(mut spec) if !spec.is_empty() && spec.len() <= 3 => {
+            let step = if spec.len() == 3 { spec.pop() } else { None };
+            let init = if spec.len() == 2 { spec.pop() } else { None };
+            match spec.pop() {
+                Some(LispValue::Name(name)) => Some((name, init, step)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
 
-        if let LispValue::Function(mut function_args) = value {
-            if let LispValue::Name(function_name) = function_args.remove(0) {
-                if let Some(value) = execute_function(state, function_name.as_str(), function_args)
-                {
-                    state.add_variable(name, value);
+/// `do` computes the initial and step values of all variables before
+/// assigning them, `do*` assigns each one before computing the next.
+fn do_loop(state: &mut State, mut args: Vec<LispValue>, sequential: bool) -> LispResult {
+    if args.len() < 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let mut variables = Vec::new();
+    match args.remove(0) {
+        LispValue::Function(specs) => {
+            for spec in specs {
+                match read_loop_variable(spec) {
+                    Some(variable) => variables.push(variable),
+                    None => return Ok(LispValue::nil()),
                 }
             }
-        } else if let LispValue::Name(name) = value {
-            let mut state_value = None;
-            if let Some(value) = state.get_value(name.as_str()) {
-                state_value = Some(value.clone());
+        }
+        _ => return Ok(LispValue::nil()),
+    }
+
+    let mut end = match args.remove(0) {
+        LispValue::Function(end) if !end.is_empty() => end,
+        _ => return Ok(LispValue::nil()),
+    };
+    let test = end.remove(0);
+
+    let mut values = Vec::new();
+    let mut init_state = state.create_child();
+    scoped(&mut init_state, |init_state| {
+        for (name, init, _) in &variables {
+            let value = match init {
+                Some(init) if sequential => evaluate(init_state, init.clone())?,
+                Some(init) => evaluate(state, init.clone())?,
+                None => LispValue::nil(),
+            };
+            if sequential {
+                init_state.add_variable(name.clone(), value.clone())?;
+            }
+            values.push(value);
+        }
+        Ok(())
+    })?;
+
+    loop {
+        let mut local_state = state.create_child();
+        let result = scoped(&mut local_state, |local_state| {
+            for ((name, _, _), value) in variables.iter().zip(values.drain(..)) {
+                local_state.add_variable(name.clone(), value)?;
             }
 
-            if let Some(value) = state_value {
-                state.add_variable(name, value);
+            let end_reached = evaluate(local_state, test.clone())?;
+            if is_true(local_state, &end_reached) {
+                let tail = evaluate_body(local_state, end.clone())?;
+                return finish_tail(local_state, tail).map(Some);
+            }
+
+            for element in &args {
+                evaluate(local_state, element.clone())?;
             }
-        } else {
-            state.add_variable(name, value);
-        }
-    }
-    None
-}
-
-pub fn equals(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
-
-    match unified_type {
-        Type::Int => Some(LispValue::Boolean(perform_equals(&convert_to_int_array(
-            args,
-        )))),
-        Type::Float => Some(LispValue::Boolean(perform_equals(&convert_to_float_array(
-            args,
-        )))),
-        Type::Boolean => Some(LispValue::Boolean(perform_equals(
-            &convert_to_boolean_array(args),
-        ))),
-        Type::String => Some(LispValue::Boolean(perform_equals(
-            &convert_to_string_array(args),
-        ))),
-        Type::None => None,
-    }
-}
-
-pub fn not_equals(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
-
-    match unified_type {
-        Type::Int => Some(LispValue::Boolean(perform_not_equals(
-            &convert_to_int_array(args),
-        ))),
-        Type::Float => Some(LispValue::Boolean(perform_not_equals(
-            &convert_to_float_array(args),
-        ))),
-        Type::Boolean => Some(LispValue::Boolean(perform_not_equals(
-            &convert_to_boolean_array(args),
-        ))),
-        Type::String => Some(LispValue::Boolean(perform_not_equals(
-            &convert_to_string_array(args),
-        ))),
-        Type::None => None,
-    }
-}
-
-pub fn grater_then(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
-
-    match unified_type {
-        Type::Int => Some(LispValue::Boolean(perform_grater_then(
-            &convert_to_int_array(args),
-        ))),
-        Type::Float => Some(LispValue::Boolean(perform_grater_then(
-            &convert_to_float_array(args),
-        ))),
-        _ => None,
-    }
-}
 
-pub fn grater_or_equals_then(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
+            let mut step_state = local_state.create_child();
+            scoped(&mut step_state, |step_state| {
+                for (name, _, step) in &variables {
+                    let value = match step {
+                        Some(step) if sequential => evaluate(step_state, step.clone())?,
+                        Some(step) => evaluate(local_state, step.clone())?,
+                        None => local_state
+                            .get_value(name.as_str())
+                            .unwrap_or_else(LispValue::nil),
+                    };
+                    if sequential {
+                        step_state.add_variable(name.clone(), value.clone())?;
+                    }
+                    values.push(value);
+                }
+                Ok(())
+            })?;
+            Ok(None)
+        })?;
 
-    match unified_type {
-        Type::Int => {
-            let array = convert_to_int_array(args);
-            Some(LispValue::Boolean(
-                perform_grater_then(&array) | perform_equals(&array),
-            ))
+        if let Some(result) = result {
+            return Ok(result);
         }
-        Type::Float => {
-            let array = convert_to_float_array(args);
-            Some(LispValue::Boolean(
-                perform_grater_then(&array) | perform_equals(&array),
-            ))
+    }
+}
+
+fn simple_loop(state: &mut State, args: Vec<LispValue>) -> LispResult {
+    loop {
+        for element in &args {
+            evaluate(state, element.clone())?;
         }
-        _ => None,
     }
 }
 
-pub fn less_or_equals_then(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
+fn while_loop(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.is_empty() {
+        return Ok(LispValue::nil());
+    }
 
-    match unified_type {
-        Type::Int => {
-            let array = convert_to_int_array(args);
-            Some(LispValue::Boolean(
-                perform_less_then(&array) | perform_equals(&array),
-            ))
+    let test = args.remove(0);
+    loop {
+        let condition = evaluate(state, test.clone())?;
+        if !is_true(state, &condition) {
+            return Ok(LispValue::nil());
         }
-        Type::Float => {
-            let array = convert_to_float_array(args);
-            Some(LispValue::Boolean(
-                perform_less_then(&array) | perform_equals(&array),
-            ))
+
+        for element in &args {
+            evaluate(state, element.clone())?;
         }
-        _ => None,
     }
 }
 
-pub fn less_then(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
-
-    match unified_type {
-        Type::Int => Some(LispValue::Boolean(perform_less_then(
-            &convert_to_int_array(args),
-        ))),
-        Type::Float => Some(LispValue::Boolean(perform_less_then(
-            &convert_to_float_array(args),
-        ))),
-        _ => None,
+/// Ends the innermost loop around it with all the values of the form.
+fn return_from_loop(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() > 1 {
+        return Ok(LispValue::nil());
     }
+    let block = state.current_block().ok_or(LispError::ReturnOutsideLoop)?;
+    let value = match args.pop() {
+        Some(form) => evaluate_values(state, form)?,
+        None => LispValue::nil(),
+    };
+    Err(LispError::Return(block, value))
 }
 
-pub fn or(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
+/// Returns the first true value, without evaluating the forms after it. The
+/// last form is left in tail position.
+pub fn or(state: &mut State, mut args: Vec<LispValue>) -> Result<Tail, LispError> {
+    let last = match args.pop() {
+        Some(last) => last,
+        None => return Ok(Tail::Value(LispValue::nil())),
+    };
 
-    match unified_type {
-        Type::Boolean => {
-            let array = convert_to_boolean_array(args);
-            let mut result = false;
-            for val in array {
-                result |= val;
-            }
-            Some(LispValue::Boolean(result))
+    for element in args {
+        let value = evaluate(state, element)?;
+        if is_true(state, &value) {
+            return Ok(Tail::Value(value));
         }
-        _ => None,
     }
+    evaluate_tail(state, last)
 }
 
-pub fn and(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
-    prepare_execution(state, &mut args);
-    let unified_type = get_args_unified_type(&args);
+/// Returns the first false value, without evaluating the forms after it, or
+/// the value of the last form.
+pub fn and(state: &mut State, mut args: Vec<LispValue>) -> Result<Tail, LispError> {
+    let last = match args.pop() {
+        Some(last) => last,
+        None => return Ok(Tail::Value(LispValue::t())),
+    };
 
-    match unified_type {
-        Type::Boolean => {
-            let array = convert_to_boolean_array(args);
-            let mut result = true;
-            for val in array {
-                result &= val;
-            }
-            Some(LispValue::Boolean(result))
+    for element in args {
+        let value = evaluate(state, element)?;
+        if !is_true(state, &value) {
+            return Ok(Tail::Value(value));
         }
-        _ => None,
     }
+    evaluate_tail(state, last)
 }
 
-pub fn not(state: &mut State, mut args: Vec<LispValue>) -> Option<LispValue> {
+pub fn not(state: &State, args: Vec<LispValue>) -> Option<bool> {
     if args.len() != 1 {
         return None;
     }
 
-    prepare_execution(state, &mut args);
+    Some(!is_true(state, &args[0]))
+}
+
+/// Predicates give `t` or `nil`, or nil when their arguments did not fit.
+fn predicate(state: &State, result: Option<bool>) -> LispValue {
+    match result {
+        Some(value) => state.boolean(value),
+        None => LispValue::nil(),
+    }
+}
 
-    if let LispValue::Boolean(value) = args[0] {
-        return Some(LispValue::Boolean(!value));
+/// Runs a function on unevaluated arguments. Special forms and macros get
+/// them as they are, and other functions get their values.
+pub fn execute(state: &mut State, function: Function, args: Vec<LispValue>) -> LispResult {
+    if function.is_special_form() {
+        return special_form(state, function, args);
     }
-    None
+
+    let args = evaluate_arguments(state, args)?;
+    call(state, function, args)
 }
 
-pub fn execute(state: &mut State, function: Function, args: Vec<LispValue>) -> Option<LispValue> {
+/// Runs a special form or a macro. It is kept out of `execute` because its
+/// stack frame is large, and ordinary calls nest much deeper.
+#[inline(never)]
+fn special_form(state: &mut State, function: Function, args: Vec<LispValue>) -> LispResult {
     match function {
-        Function::Add => add(state, args),
-        Function::Sub => sub(state, args),
-        Function::Mul => mul(state, args),
-        Function::Div => div(state, args),
-        Function::Grater => grater_then(state, args),
-        Function::GraterOrEquals => grater_or_equals_then(state, args),
-        Function::Less => less_then(state, args),
-        Function::LessOrEquals => less_or_equals_then(state, args),
-        Function::Equals => equals(state, args),
-        Function::NotEquals => not_equals(state, args),
-        Function::Or => or(state, args),
-        Function::And => and(state, args),
-        Function::Not => not(state, args),
-        //        Function::Print => print(state, args),
-        Function::Println => println(state, args),
-        Function::Defvar => define_var(state, args),
+        Function::Defvar => define_var(state, args, false),
+        Function::Defparameter => define_var(state, args, true),
+        Function::Defconstant => define_constant(state, args),
         Function::Defun => create_custom_function(state, args),
-        // Function::Setq => None,
-        Function::Custom(parameters, code) => {
-            execute_custom_function(state, args, parameters, code)
+        Function::Defmacro => define_macro(state, args),
+        Function::Quote => quote(args),
+        Function::MultipleValueBind => {
+            let tail = multiple_value_bind(state, args)?;
+            finish_tail(state, tail)
+        }
+        Function::MultipleValueList => multiple_value_list(state, args),
+        Function::MultipleValueCall => multiple_value_call(state, args),
+        Function::NthValue => nth_value(state, args),
+        Function::Macro(lambda_list, body, environment) => {
+            let expansion = expand_macro(state, lambda_list, body, environment, args)?;
+            evaluate_values(state, expansion)
+        }
+        Function::Lambda => Ok(create_lambda(state, args).unwrap_or_else(LispValue::nil)),
+        Function::SharpQuote => function_ref(state, args),
+        Function::Setq => setq(state, args),
+        Function::Let => {
+            let tail = let_block(state, args, false)?;
+            finish_tail(state, tail)
+        }
+        Function::LetStar => {
+            let tail = let_block(state, args, true)?;
+            finish_tail(state, tail)
         }
+        Function::If => {
+            let tail = if_block(state, args)?;
+            finish_tail(state, tail)
+        }
+        Function::Progn => {
+            let tail = progn(state, args)?;
+            finish_tail(state, tail)
+        }
+        Function::Or => {
+            let tail = or(state, args)?;
+            finish_tail(state, tail)
+        }
+        Function::And => {
+            let tail = and(state, args)?;
+            finish_tail(state, tail)
+        }
+        Function::Dotimes => loop_block(state, |state| dotimes(state, args)),
+        Function::Dolist => loop_block(state, |state| dolist(state, args)),
+        Function::Do => loop_block(state, |state| do_loop(state, args, false)),
+        Function::DoStar => loop_block(state, |state| do_loop(state, args, true)),
+        Function::Loop => loop_block(state, |state| simple_loop(state, args)),
+        Function::While => loop_block(state, |state| while_loop(state, args)),
+        Function::Return => return_from_loop(state, args),
+        function => call(state, function, args),
+    }
+}
+
+/// Calls a function with already evaluated arguments.
+pub fn call(state: &mut State, function: Function, args: Vec<LispValue>) -> LispResult {
+    match function {
+        Function::Custom(parameters, code, environment) => {
+            execute_custom_function(state, args, parameters, code, environment)
+        }
+        function => call_builtin(state, function, args),
+    }
+}
+
+/// Calls a builtin function. Its large stack frame stays out of calls to
+/// custom functions, which nest deeply.
+#[inline(never)]
+fn call_builtin(state: &mut State, function: Function, args: Vec<LispValue>) -> LispResult {
+    match function {
+        Function::Add => add(args),
+        Function::Sub => sub(args),
+        Function::Mul => mul(args),
+        Function::Div => div(args),
+        Function::Floor => divide_rounding("floor", Rounding::Floor, args),
+        Function::Truncate => divide_rounding("truncate", Rounding::Truncate, args),
+        Function::Round => divide_rounding("round", Rounding::Round, args),
+        Function::Ceiling => divide_rounding("ceiling", Rounding::Ceiling, args),
+        Function::Mod => remainder("mod", Rounding::Floor, args),
+        Function::Rem => remainder("rem", Rounding::Truncate, args),
+        Function::OnePlus => increment("1+", 1, args),
+        Function::OneMinus => increment("1-", -1, args),
+        Function::Abs => abs(args),
+        Function::Min => extreme("min", Ordering::Less, args),
+        Function::Max => extreme("max", Ordering::Greater, args),
+        Function::Expt => expt(args),
+        Function::Sqrt => sqrt(args),
+        Function::Isqrt => isqrt(args),
+        Function::Exp => exp(args),
+        Function::Log => log(args),
+        Function::Sin => float_function("sin", f64::sin, args),
+        Function::Cos => float_function("cos", f64::cos, args),
+        Function::Tan => float_function("tan", f64::tan, args),
+        Function::Asin => float_function("asin", f64::asin, args),
+        Function::Acos => float_function("acos", f64::acos, args),
+        Function::Atan => atan(args),
+        Function::Sinh => float_function("sinh", f64::sinh, args),
+        Function::Cosh => float_function("cosh", f64::cosh, args),
+        Function::Tanh => float_function("tanh", f64::tanh, args),
+        Function::Asinh => float_function("asinh", f64::asinh, args),
+        Function::Acosh => float_function("acosh", f64::acosh, args),
+        Function::Atanh => float_function("atanh", f64::atanh, args),
+        Function::Gcd => gcd(args),
+        Function::Lcm => lcm(args),
+        Function::Signum => signum(args),
+        Function::Zerop => zerop(args).map(|result| predicate(state, result)),
+        Function::Plusp => {
+            sign_test("plusp", Ordering::is_gt, args).map(|result| predicate(state, result))
+        }
+        Function::Minusp => {
+            sign_test("minusp", Ordering::is_lt, args).map(|result| predicate(state, result))
+        }
+        Function::Evenp => parity("evenp", false, args).map(|result| predicate(state, result)),
+        Function::Oddp => parity("oddp", true, args).map(|result| predicate(state, result)),
+        Function::Numerator => numerator(args),
+        Function::Denominator => denominator(args),
+        Function::Rational => to_rational("rational", Ratio::from_f64, args),
+        Function::Rationalize => to_rational("rationalize", Ratio::rationalize, args),
+        Function::Complex => complex(args),
+        Function::Realpart => part("realpart", false, args),
+        Function::Imagpart => part("imagpart", true, args),
+        Function::Conjugate => conjugate(args),
+        Function::Phase => phase(args),
+        Function::Logand => logical("logand", -1, |a, b| a & b, args),
+        Function::Logior => logical("logior", 0, |a, b| a | b, args),
+        Function::Logxor => logical("logxor", 0, |a, b| a ^ b, args),
+        Function::Lognot => lognot(args),
+        Function::Logtest => logtest(args).map(|result| predicate(state, result)),
+        Function::Ash => ash(args),
+        Function::Logbitp => logbitp(args).map(|result| predicate(state, result)),
+        Function::Logcount => logcount(args),
+        Function::IntegerLength => integer_length(args),
+        Function::Byte => byte(args),
+        Function::Ldb => ldb(args),
+        Function::Dpb => dpb(args),
+        Function::Random => random(state, args),
+        Function::MakeRandomState => make_random_state(state, args),
+        Function::ParseInteger => parse_integer(state, args),
+        Function::ReadFromString => read_from_string(state, args),
+        Function::WriteToString => write_string(state, "write-to-string", true, args),
+        Function::PrincToString => write_string(state, "princ-to-string", false, args),
+        Function::Float => float(args),
+        Function::Coerce => coerce(args),
+        Function::DecodeFloat => decode_float(args),
+        Function::Grater => {
+            compare(">", args, Ordering::is_gt).map(|result| predicate(state, result))
+        }
+        Function::GraterOrEquals => {
+            compare(">=", args, Ordering::is_ge).map(|result| predicate(state, result))
+        }
+        Function::Less => {
+            compare("<", args, Ordering::is_lt).map(|result| predicate(state, result))
+        }
+        Function::LessOrEquals => {
+            compare("<=", args, Ordering::is_le).map(|result| predicate(state, result))
+        }
+        Function::Equals => equals(args).map(|result| predicate(state, result)),
+        Function::NotEquals => not_equals(args).map(|result| predicate(state, result)),
+        Function::Not => Ok(predicate(state, not(state, args))),
+        Function::Println => println(state, args),
+        Function::Funcall => funcall(state, args),
+        Function::Apply => apply(state, args),
+        Function::Macroexpand1 => macroexpand_1(state, args),
+        Function::Macroexpand => macroexpand(state, args),
+        Function::Gensym => gensym(state, args),
+        Function::Eval => eval(state, args),
+        Function::Values => values(args),
+        Function::Documentation => documentation(state, args),
+        Function::Describe => describe(state, args),
+        Function::Cons => cons(args),
+        Function::Car => car("car", args),
+        Function::Cdr => cdr("cdr", args),
+        Function::First => car("first", args),
+        Function::Rest => cdr("rest", args),
+        Function::List => list(args),
+        Function::ListStar => list_star(args),
+        Function::Append => append(args),
+        Function::Length => length(args),
+        Function::Nth => nth(args),
+        Function::Nthcdr => nthcdr(args),
+        Function::Last => last(args),
+        Function::Butlast => butlast(args),
+        Function::Reverse => reverse("reverse", args),
+        Function::Nreverse => reverse("nreverse", args),
+        Function::Member => member(args),
+        Function::CopyList => copy_list(args),
+        Function::Null => Ok(predicate(state, null(args))),
+        Function::Endp => endp(args).map(|result| predicate(state, result)),
+        Function::Custom(..)
+        | Function::Defvar
+        | Function::Defparameter
+        | Function::Defconstant
+        | Function::Defun
+        | Function::Defmacro
+        | Function::Quote
+        | Function::MultipleValueBind
+        | Function::MultipleValueList
+        | Function::MultipleValueCall
+        | Function::NthValue
+        | Function::Macro(..)
+        | Function::Lambda
+        | Function::SharpQuote
+        | Function::Setq
+        | Function::Let
+        | Function::LetStar
+        | Function::If
+        | Function::Progn
+        | Function::Or
+        | Function::And
+        | Function::Dotimes
+        | Function::Dolist
+        | Function::Do
+        | Function::DoStar
+        | Function::Loop
+        | Function::While
+        | Function::Return => Ok(LispValue::nil()),
     }
 }
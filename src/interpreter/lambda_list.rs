@@ -0,0 +1,335 @@
+use super::types::*;
+use crate::parser::LispValue;
+
+/// A required parameter. Macro lambda lists may use a nested lambda list in
+/// its place, which destructures the argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parameter {
+    Variable(String),
+    Destructure(Box<LambdaList>),
+}
+
+/// An `&optional` or `&aux` parameter. `&aux` parameters never have a
+/// supplied-p variable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionalParameter {
+    pub name: String,
+    pub default: Option<LispValue>,
+    pub supplied: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyParameter {
+    pub keyword: String,
+    pub name: String,
+    pub default: Option<LispValue>,
+    pub supplied: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LambdaList {
+    pub required: Vec<Parameter>,
+    pub optional: Vec<OptionalParameter>,
+    pub rest: Option<String>,
+    /// Present when the lambda list has `&key`, even without key parameters.
+    pub keys: Option<Vec<KeyParameter>>,
+    pub allow_other_keys: bool,
+    pub aux: Vec<OptionalParameter>,
+}
+
+impl LambdaList {
+    /// Describes the number of arguments the lambda list accepts.
+    fn expected_arguments(&self) -> String {
+        let required = self.required.len();
+        match self.optional.len() {
+            _ if self.rest.is_some() || self.keys.is_some() => format!("at least {}", required),
+            0 => required.to_string(),
+            optional => format!("{} to {}", required, required + optional),
+        }
+    }
+}
+
+#[derive(PartialEq, PartialOrd)]
+enum Section {
+    Required,
+    Optional,
+    Rest,
+    Key,
+    Aux,
+}
+
+fn read_parameter(value: LispValue, destructuring: bool) -> Option<Parameter> {
+    match value {
+        LispValue::Name(name) => Some(Parameter::Variable(name)),
+        value @ LispValue::Function(_) | value @ LispValue::DottedList(..) if destructuring => {
+            let lambda_list = read_lambda_list(value, true)?;
+            Some(Parameter::Destructure(Box::new(lambda_list)))
+        }
+        _ => None,
+    }
+}
+
+type Spec = (LispValue, Option<LispValue>, Option<String>);
+
+/// Reads `name` or `(name [default [supplied-p]])`, leaving `name` unchecked.
+fn read_spec(value: LispValue) -> Option<Spec> {
+    match value {
+        LispValue::Function(mut spec) if !spec.is_empty() && spec.len() <= 3 => {
+            let supplied = match spec.len() {
+                3 => match spec.pop() {
+                    Some(LispValue::Name(supplied)) => Some(supplied),
+                    _ => return None,
+                },
+                _ => None,
+            };
+            let default = if spec.len() == 2 { spec.pop() } else { None };
+            Some((spec.pop()?, default, supplied))
+        }
+        name => Some((name, None, None)),
+    }
+}
+
+fn read_optional(value: LispValue) -> Option<OptionalParameter> {
+    match read_spec(value)? {
+        (LispValue::Name(name), default, supplied) => Some(OptionalParameter {
+            name,
+            default,
+            supplied,
+        }),
+        _ => None,
+    }
+}
+
+/// Reads a key parameter, where the name may be `(keyword name)` to use a
+/// keyword different from `:name`.
+fn read_key(value: LispValue) -> Option<KeyParameter> {
+    let (name, default, supplied) = read_spec(value)?;
+    let (keyword, name) = match name {
+        LispValue::Name(name) => (format!(":{}", name), name),
+        LispValue::Function(names) => match names.as_slice() {
+            [LispValue::Name(keyword), LispValue::Name(name)] => (keyword.clone(), name.clone()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(KeyParameter {
+        keyword,
+        name,
+        default,
+        supplied,
+    })
+}
+
+fn read_aux(value: LispValue) -> Option<OptionalParameter> {
+    match read_optional(value)? {
+        OptionalParameter {
+            supplied: Some(_), ..
+        } => None,
+        parameter => Some(parameter),
+    }
+}
+
+/// Reads a lambda list with required, `&optional`, `&rest`, `&key` and `&aux`
+/// parameters. Macro lambda lists are `destructuring`: they also accept
+/// `&body` for `&rest`, nested lambda lists for required parameters and
+/// `(a . rest)` for `(a &rest rest)`.
+pub fn read_lambda_list(value: LispValue, destructuring: bool) -> Option<LambdaList> {
+    let (items, tail) = match value {
+        LispValue::Function(items) => (items, None),
+        LispValue::DottedList(items, tail) if destructuring => (items, Some(*tail)),
+        _ => return None,
+    };
+
+    let mut lambda_list = LambdaList::default();
+    let mut section = Section::Required;
+    for item in items {
+        let next = match &item {
+            LispValue::Name(name) if name == "&optional" => Some(Section::Optional),
+            LispValue::Name(name) if name == "&rest" => Some(Section::Rest),
+            LispValue::Name(name) if name == "&body" && destructuring => Some(Section::Rest),
+            LispValue::Name(name) if name == "&key" => Some(Section::Key),
+            LispValue::Name(name) if name == "&aux" => Some(Section::Aux),
+            LispValue::Name(name) if name == "&allow-other-keys" => {
+                if section != Section::Key || lambda_list.allow_other_keys {
+                    return None;
+                }
+                lambda_list.allow_other_keys = true;
+                continue;
+            }
+            _ => None,
+        };
+
+        if let Some(next) = next {
+            if next <= section || (section == Section::Rest && lambda_list.rest.is_none()) {
+                return None;
+            }
+            if next == Section::Key {
+                lambda_list.keys = Some(Vec::new());
+            }
+            section = next;
+            continue;
+        }
+
+        match (&section, item) {
+            (Section::Required, item) => lambda_list
+                .required
+                .push(read_parameter(item, destructuring)?),
+            (Section::Optional, item) => lambda_list.optional.push(read_optional(item)?),
+            (Section::Rest, LispValue::Name(name)) if lambda_list.rest.is_none() => {
+                lambda_list.rest = Some(name)
+            }
+            (Section::Key, item) if !lambda_list.allow_other_keys => {
+                lambda_list.keys.as_mut()?.push(read_key(item)?)
+            }
+            (Section::Aux, item) => lambda_list.aux.push(read_aux(item)?),
+            _ => return None,
+        }
+    }
+
+    if section == Section::Rest && lambda_list.rest.is_none() {
+        return None;
+    }
+    match tail {
+        Some(LispValue::Name(name)) if section < Section::Rest => lambda_list.rest = Some(name),
+        Some(_) => return None,
+        None => {}
+    }
+    Some(lambda_list)
+}
+
+/// Binds `args` to the parameters of `lambda_list` in `state`. Defaults are
+/// evaluated in `state` too, so they see the parameters bound before them.
+pub fn bind_arguments(
+    state: &mut State,
+    lambda_list: &LambdaList,
+    args: Vec<LispValue>,
+) -> Result<(), LispError> {
+    let required = lambda_list.required.len();
+    let positional = required + lambda_list.optional.len();
+    let variadic = lambda_list.rest.is_some() || lambda_list.keys.is_some();
+    if args.len() < required || (!variadic && args.len() > positional) {
+        return Err(LispError::ArgumentCount {
+            expected: lambda_list.expected_arguments(),
+            given: args.len(),
+        });
+    }
+
+    let mut args = args.into_iter();
+    for (parameter, arg) in lambda_list.required.iter().zip(args.by_ref()) {
+        bind_parameter(state, parameter, arg)?;
+    }
+
+    for parameter in &lambda_list.optional {
+        let value = args.next();
+        bind_optional(
+            state,
+            &parameter.name,
+            value,
+            &parameter.default,
+            &parameter.supplied,
+        )?;
+    }
+
+    let rest: Vec<LispValue> = args.collect();
+    if let Some(name) = &lambda_list.rest {
+        state.add_variable(name.clone(), LispValue::Function(rest.clone()))?;
+    }
+
+    if let Some(keys) = &lambda_list.keys {
+        bind_keys(state, keys, lambda_list.allow_other_keys, rest)?;
+    }
+
+    for parameter in &lambda_list.aux {
+        bind_optional(state, &parameter.name, None, &parameter.default, &None)?;
+    }
+    Ok(())
+}
+
+fn bind_parameter(
+    state: &mut State,
+    parameter: &Parameter,
+    arg: LispValue,
+) -> Result<(), LispError> {
+    match parameter {
+        Parameter::Variable(name) => {
+            state.add_variable(name.clone(), arg)?;
+            Ok(())
+        }
+        Parameter::Destructure(lambda_list) => match arg {
+            LispValue::Function(items) => bind_arguments(state, lambda_list, items),
+            value => Err(type_error("defmacro", "list", value)),
+        },
+    }
+}
+
+/// Binds `name` to the given value, or to its default when there is none.
+fn bind_optional(
+    state: &mut State,
+    name: &str,
+    value: Option<LispValue>,
+    default: &Option<LispValue>,
+    supplied: &Option<String>,
+) -> Result<(), LispError> {
+    let is_supplied = value.is_some();
+    let value = match (value, default) {
+        (Some(value), _) => value,
+        (None, Some(default)) => evaluate(state, default.clone())?,
+        (None, None) => LispValue::nil(),
+    };
+
+    state.add_variable(name.to_string(), value)?;
+    if let Some(supplied) = supplied {
+        let is_supplied = state.boolean(is_supplied);
+        state.add_variable(supplied.clone(), is_supplied)?;
+    }
+    Ok(())
+}
+
+/// Binds key parameters from keyword and value pairs. The first pair for a
+/// keyword wins. Unknown keywords are an error unless `&allow-other-keys` was
+/// given, or the call passes a true `:allow-other-keys`.
+fn bind_keys(
+    state: &mut State,
+    keys: &[KeyParameter],
+    allow_other_keys: bool,
+    args: Vec<LispValue>,
+) -> Result<(), LispError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(LispError::ArgumentCount {
+            expected: "an even number of keyword arguments".to_string(),
+            given: args.len(),
+        });
+    }
+
+    let pairs: Vec<(&LispValue, &LispValue)> =
+        args.chunks(2).map(|pair| (&pair[0], &pair[1])).collect();
+    let find = |keyword: &str| {
+        pairs
+            .iter()
+            .find(|(key, _)| matches!(key, LispValue::Name(name) if name == keyword))
+            .map(|(_, value)| (*value).clone())
+    };
+
+    let allow_other_keys =
+        allow_other_keys || find(":allow-other-keys").is_some_and(|value| is_true(state, &value));
+    if !allow_other_keys {
+        for (key, _) in &pairs {
+            let known = match key {
+                LispValue::Name(name) => {
+                    name == ":allow-other-keys" || keys.iter().any(|key| key.keyword == *name)
+                }
+                _ => false,
+            };
+            if !known {
+                return Err(LispError::UnknownKeyword((*key).clone()));
+            }
+        }
+    }
+
+    for key in keys {
+        let value = find(key.keyword.as_str());
+        bind_optional(state, &key.name, value, &key.default, &key.supplied)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use super::types::*;
+use crate::parser::LispValue;
+
+/// Returns the elements of a proper list, or a type error naming `function`.
+fn proper_list(function: &str, value: LispValue) -> Result<Vec<LispValue>, LispError> {
+    match value {
+        LispValue::Function(list) => Ok(list),
+        value => Err(type_error(function, "list", value)),
+    }
+}
+
+fn index(function: &str, value: LispValue) -> Result<usize, LispError> {
+    match value {
+        LispValue::Int(i) if i >= 0 => Ok(i as usize),
+        value => Err(type_error(function, "unsigned integer", value)),
+    }
+}
+
+/// Puts `items` in front of `tail`, which gives a dotted list when `tail` is
+/// not a list itself.
+fn prepend(mut items: Vec<LispValue>, tail: LispValue) -> LispValue {
+    if items.is_empty() {
+        return tail;
+    }
+
+    match tail {
+        LispValue::Function(list) => {
+            items.extend(list);
+            LispValue::Function(items)
+        }
+        LispValue::DottedList(list, tail) => {
+            items.extend(list);
+            LispValue::DottedList(items, tail)
+        }
+        tail => LispValue::DottedList(items, Box::new(tail)),
+    }
+}
+
+/// Drops the first `count` conses of a list.
+fn drop_conses(function: &str, value: LispValue, count: usize) -> Result<LispValue, LispError> {
+    match value {
+        LispValue::Function(list) => {
+            let rest = list.into_iter().skip(count).collect();
+            Ok(LispValue::Function(rest))
+        }
+        LispValue::DottedList(list, tail) => {
+            if count >= list.len() {
+                return Ok(*tail);
+            }
+            let rest = list.into_iter().skip(count).collect();
+            Ok(LispValue::DottedList(rest, tail))
+        }
+        value => Err(type_error(function, "list", value)),
+    }
+}
+
+pub fn cons(mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 2, Some(2))?;
+
+    let tail = args.remove(1);
+    Ok(prepend(args, tail))
+}
+
+pub fn car(function: &str, mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 1, Some(1))?;
+
+    match args.remove(0) {
+        LispValue::Function(list) | LispValue::DottedList(list, _) => {
+            Ok(list.into_iter().next().unwrap_or_else(LispValue::nil))
+        }
+        value => Err(type_error(function, "list", value)),
+    }
+}
+
+pub fn cdr(function: &str, mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 1, Some(1))?;
+
+    drop_conses(function, args.remove(0), 1)
+}
+
+pub fn list(args: Vec<LispValue>) -> LispResult {
+    Ok(LispValue::Function(args))
+}
+
+pub fn list_star(mut args: Vec<LispValue>) -> LispResult {
+    match args.pop() {
+        Some(tail) => Ok(prepend(args, tail)),
+        None => Ok(LispValue::nil()),
+    }
+}
+
+pub fn append(mut args: Vec<LispValue>) -> LispResult {
+    let tail = match args.pop() {
+        Some(tail) => tail,
+        None => return Ok(LispValue::nil()),
+    };
+
+    let mut items = Vec::new();
+    for value in args {
+        items.extend(proper_list("append", value)?);
+    }
+    Ok(prepend(items, tail))
+}
+
+pub fn length(mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 1, Some(1))?;
+
+    match args.remove(0) {
+        LispValue::Function(list) => Ok(LispValue::Int(list.len() as i64)),
+        LispValue::String(s) => Ok(LispValue::Int(s.chars().count() as i64)),
+        value => Err(type_error("length", "sequence", value)),
+    }
+}
+
+pub fn nth(mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 2, Some(2))?;
+
+    let n = index("nth", args.remove(0))?;
+    let rest = drop_conses("nth", args.remove(0), n)?;
+    car("nth", vec![rest])
+}
+
+pub fn nthcdr(mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 2, Some(2))?;
+
+    let n = index("nthcdr", args.remove(0))?;
+    drop_conses("nthcdr", args.remove(0), n)
+}
+
+fn conses_count(function: &str, value: &LispValue) -> Result<usize, LispError> {
+    match value {
+        LispValue::Function(list) => Ok(list.len()),
+        LispValue::DottedList(list, _) => Ok(list.len()),
+        value => Err(type_error(function, "list", value.clone())),
+    }
+}
+
+pub fn last(mut args: Vec<LispValue>) -> LispResult {
+    if args.is_empty() || args.len() > 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let n = match args.len() {
+        2 => index("last", args.remove(1))?,
+        _ => 1,
+    };
+    let list = args.remove(0);
+    let count = conses_count("last", &list)?;
+    drop_conses("last", list, count.saturating_sub(n))
+}
+
+pub fn butlast(mut args: Vec<LispValue>) -> LispResult {
+    if args.is_empty() || args.len() > 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let n = match args.len() {
+        2 => index("butlast", args.remove(1))?,
+        _ => 1,
+    };
+    let mut list = match args.remove(0) {
+        LispValue::Function(list) => list,
+        LispValue::DottedList(list, _) => list,
+        value => return Err(type_error("butlast", "list", value)),
+    };
+    list.truncate(list.len().saturating_sub(n));
+    Ok(LispValue::Function(list))
+}
+
+pub fn reverse(function: &str, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let mut list = proper_list(function, args.remove(0))?;
+    list.reverse();
+    Ok(LispValue::Function(list))
+}
+
+pub fn member(mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let list = args.remove(1);
+    let item = args.remove(0);
+    let position = match &list {
+        LispValue::Function(items) | LispValue::DottedList(items, _) => {
+            items.iter().position(|value| *value == item)
+        }
+        value => return Err(type_error("member", "list", value.clone())),
+    };
+
+    match position {
+        Some(position) => drop_conses("member", list, position),
+        None => Ok(LispValue::nil()),
+    }
+}
+
+pub fn copy_list(mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    match args.remove(0) {
+        list @ LispValue::Function(_) | list @ LispValue::DottedList(..) => Ok(list),
+        value => Err(type_error("copy-list", "list", value)),
+    }
+}
+
+pub fn null(args: Vec<LispValue>) -> Option<bool> {
+    if args.len() != 1 {
+        return None;
+    }
+
+    Some(args[0].is_nil())
+}
+
+pub fn endp(mut args: Vec<LispValue>) -> Result<Option<bool>, LispError> {
+    if args.len() != 1 {
+        return Ok(None);
+    }
+
+    match args.remove(0) {
+        LispValue::Function(list) => Ok(Some(list.is_empty())),
+        LispValue::DottedList(..) => Ok(Some(false)),
+        value => Err(type_error("endp", "list", value)),
+    }
+}
@@ -0,0 +1,115 @@
+use super::documentation::*;
+use super::functions::*;
+use super::lambda_list::*;
+use super::types::*;
+use crate::parser::LispValue;
+
+pub fn define_macro(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() < 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let name = match args.remove(0) {
+        LispValue::Name(name) => name,
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let lambda_list = match read_lambda_list(args.remove(0), true) {
+        Some(lambda_list) => lambda_list,
+        None => return Ok(LispValue::nil()),
+    };
+
+    let documentation = read_docstring(&mut args);
+    state.set_documentation(&name, DocType::Function, documentation);
+
+    let environment = state.clone();
+    state.add_function(
+        name.clone(),
+        Function::Macro(Box::new(lambda_list), args, environment),
+    );
+    Ok(LispValue::Name(name))
+}
+
+/// Runs the body of a macro with the unevaluated arguments of its call and
+/// returns the form it expands to.
+pub fn expand_macro(
+    state: &mut State,
+    lambda_list: Box<LambdaList>,
+    body: Vec<LispValue>,
+    environment: State,
+    args: Vec<LispValue>,
+) -> LispResult {
+    state.enter_call()?;
+    let result = run_expander(lambda_list, body, environment, args);
+    state.leave_call();
+    result
+}
+
+fn run_expander(
+    lambda_list: Box<LambdaList>,
+    body: Vec<LispValue>,
+    environment: State,
+    args: Vec<LispValue>,
+) -> LispResult {
+    let mut local_state = environment.create_child();
+    scoped(&mut local_state, |local_state| {
+        bind_arguments(local_state, &lambda_list, args)?;
+        let tail = evaluate_body(local_state, body)?;
+        finish_tail(local_state, tail)
+    })
+}
+
+/// Expands `form` once when it is a macro call. The flag tells whether it
+/// was one.
+fn expand_once(state: &mut State, form: LispValue) -> Result<(LispValue, bool), LispError> {
+    if let LispValue::Function(items) = &form {
+        if let Some(LispValue::Name(name)) = items.first() {
+            if let Some(Function::Macro(lambda_list, body, environment)) = state.get_function(name)
+            {
+                let args = items[1..].to_vec();
+                let expansion = expand_macro(state, lambda_list, body, environment, args)?;
+                return Ok((expansion, true));
+            }
+        }
+    }
+    Ok((form, false))
+}
+
+pub fn macroexpand_1(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    Ok(expand_once(state, args.remove(0))?.0)
+}
+
+/// Expands `form` until it is no longer a macro call.
+pub fn macroexpand(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let mut form = args.remove(0);
+    loop {
+        match expand_once(state, form)? {
+            (expansion, true) => form = expansion,
+            (form, false) => return Ok(form),
+        }
+    }
+}
+
+/// Creates a fresh symbol, printed as `#:G1`, for names in macro expansions
+/// that must not collide with the names of the caller.
+pub fn gensym(state: &State, args: Vec<LispValue>) -> LispResult {
+    let prefix = match args.first() {
+        None => "G".to_string(),
+        Some(LispValue::String(prefix)) => prefix.clone(),
+        Some(_) => return Ok(LispValue::nil()),
+    };
+
+    Ok(LispValue::Name(format!(
+        "#:{}{}",
+        prefix,
+        state.next_gensym()
+    )))
+}
@@ -1,16 +1,103 @@
 mod basic;
+mod bignum;
+mod bits;
+mod complex;
+mod conversion;
+mod documentation;
+mod floats;
 mod functions;
+mod lambda_list;
+mod lists;
+mod macros;
+mod numbers;
+mod random;
+mod ratio;
 mod types;
+mod values;
 
-use crate::parser::{parse, LispValue};
+use crate::parser::{read_from_string, LispValue};
+pub use bignum::BigInt;
+pub use complex::Complex;
+pub use floats::FloatFormat;
+pub use random::RandomState;
+pub use ratio::Ratio;
+use std::panic;
+use std::thread;
 use types::*;
+pub use types::{to_print_string, Function};
 
-#[cfg_attr(rustfmt, rustfmt_skip)]
+#[rustfmt::skip]
 fn initialize_functions(state: &mut State) {
     state.add_function("+".to_string(), Function::Add);
     state.add_function("-".to_string(), Function::Sub);
     state.add_function("*".to_string(), Function::Mul);
     state.add_function("/".to_string(), Function::Div);
+    state.add_function("floor".to_string(), Function::Floor);
+    state.add_function("truncate".to_string(), Function::Truncate);
+    state.add_function("round".to_string(), Function::Round);
+    state.add_function("ceiling".to_string(), Function::Ceiling);
+    state.add_function("mod".to_string(), Function::Mod);
+    state.add_function("rem".to_string(), Function::Rem);
+    state.add_function("1+".to_string(), Function::OnePlus);
+    state.add_function("1-".to_string(), Function::OneMinus);
+    state.add_function("abs".to_string(), Function::Abs);
+    state.add_function("min".to_string(), Function::Min);
+    state.add_function("max".to_string(), Function::Max);
+    state.add_function("expt".to_string(), Function::Expt);
+    state.add_function("sqrt".to_string(), Function::Sqrt);
+    state.add_function("isqrt".to_string(), Function::Isqrt);
+    state.add_function("exp".to_string(), Function::Exp);
+    state.add_function("log".to_string(), Function::Log);
+    state.add_function("sin".to_string(), Function::Sin);
+    state.add_function("cos".to_string(), Function::Cos);
+    state.add_function("tan".to_string(), Function::Tan);
+    state.add_function("asin".to_string(), Function::Asin);
+    state.add_function("acos".to_string(), Function::Acos);
+    state.add_function("atan".to_string(), Function::Atan);
+    state.add_function("sinh".to_string(), Function::Sinh);
+    state.add_function("cosh".to_string(), Function::Cosh);
+    state.add_function("tanh".to_string(), Function::Tanh);
+    state.add_function("asinh".to_string(), Function::Asinh);
+    state.add_function("acosh".to_string(), Function::Acosh);
+    state.add_function("atanh".to_string(), Function::Atanh);
+    state.add_function("gcd".to_string(), Function::Gcd);
+    state.add_function("lcm".to_string(), Function::Lcm);
+    state.add_function("signum".to_string(), Function::Signum);
+    state.add_function("zerop".to_string(), Function::Zerop);
+    state.add_function("plusp".to_string(), Function::Plusp);
+    state.add_function("minusp".to_string(), Function::Minusp);
+    state.add_function("evenp".to_string(), Function::Evenp);
+    state.add_function("oddp".to_string(), Function::Oddp);
+    state.add_function("numerator".to_string(), Function::Numerator);
+    state.add_function("denominator".to_string(), Function::Denominator);
+    state.add_function("rational".to_string(), Function::Rational);
+    state.add_function("rationalize".to_string(), Function::Rationalize);
+    state.add_function("complex".to_string(), Function::Complex);
+    state.add_function("realpart".to_string(), Function::Realpart);
+    state.add_function("imagpart".to_string(), Function::Imagpart);
+    state.add_function("conjugate".to_string(), Function::Conjugate);
+    state.add_function("phase".to_string(), Function::Phase);
+    state.add_function("logand".to_string(), Function::Logand);
+    state.add_function("logior".to_string(), Function::Logior);
+    state.add_function("logxor".to_string(), Function::Logxor);
+    state.add_function("lognot".to_string(), Function::Lognot);
+    state.add_function("logtest".to_string(), Function::Logtest);
+    state.add_function("ash".to_string(), Function::Ash);
+    state.add_function("logbitp".to_string(), Function::Logbitp);
+    state.add_function("logcount".to_string(), Function::Logcount);
+    state.add_function("integer-length".to_string(), Function::IntegerLength);
+    state.add_function("byte".to_string(), Function::Byte);
+    state.add_function("ldb".to_string(), Function::Ldb);
+    state.add_function("dpb".to_string(), Function::Dpb);
+    state.add_function("random".to_string(), Function::Random);
+    state.add_function("make-random-state".to_string(), Function::MakeRandomState);
+    state.add_function("parse-integer".to_string(), Function::ParseInteger);
+    state.add_function("read-from-string".to_string(), Function::ReadFromString);
+    state.add_function("write-to-string".to_string(), Function::WriteToString);
+    state.add_function("princ-to-string".to_string(), Function::PrincToString);
+    state.add_function("float".to_string(), Function::Float);
+    state.add_function("coerce".to_string(), Function::Coerce);
+    state.add_function("decode-float".to_string(), Function::DecodeFloat);
     state.add_function(">".to_string(), Function::Grater);
     state.add_function(">=".to_string(), Function::GraterOrEquals);
     state.add_function("<".to_string(), Function::Less);
@@ -22,24 +109,2100 @@ fn initialize_functions(state: &mut State) {
     state.add_function("not".to_string(), Function::Not);
     state.add_function("add".to_string(), Function::Add);
     state.add_function("sub".to_string(), Function::Sub);
-    state.add_function("div".to_string(), Function::Mul);
-    state.add_function("mul".to_string(), Function::Div);
+    state.add_function("div".to_string(), Function::Div);
+    state.add_function("mul".to_string(), Function::Mul);
     state.add_function("print".to_string(), Function::Println);
     state.add_function("defvar".to_string(), Function::Defvar);
-	state.add_function("defun".to_string(), Function::Defun);
+    state.add_function("defparameter".to_string(), Function::Defparameter);
+    state.add_function("defconstant".to_string(), Function::Defconstant);
+    state.add_function("defun".to_string(), Function::Defun);
+    state.add_function("setq".to_string(), Function::Setq);
+    state.add_function("let".to_string(), Function::Let);
+    state.add_function("let*".to_string(), Function::LetStar);
+    state.add_function("if".to_string(), Function::If);
+    state.add_function("progn".to_string(), Function::Progn);
+    state.add_function("dotimes".to_string(), Function::Dotimes);
+    state.add_function("dolist".to_string(), Function::Dolist);
+    state.add_function("do".to_string(), Function::Do);
+    state.add_function("do*".to_string(), Function::DoStar);
+    state.add_function("loop".to_string(), Function::Loop);
+    state.add_function("while".to_string(), Function::While);
+    state.add_function("return".to_string(), Function::Return);
+    state.add_function("lambda".to_string(), Function::Lambda);
+    state.add_function("cons".to_string(), Function::Cons);
+    state.add_function("car".to_string(), Function::Car);
+    state.add_function("cdr".to_string(), Function::Cdr);
+    state.add_function("first".to_string(), Function::First);
+    state.add_function("rest".to_string(), Function::Rest);
+    state.add_function("list".to_string(), Function::List);
+    state.add_function("list*".to_string(), Function::ListStar);
+    state.add_function("append".to_string(), Function::Append);
+    state.add_function("length".to_string(), Function::Length);
+    state.add_function("nth".to_string(), Function::Nth);
+    state.add_function("nthcdr".to_string(), Function::Nthcdr);
+    state.add_function("last".to_string(), Function::Last);
+    state.add_function("butlast".to_string(), Function::Butlast);
+    state.add_function("reverse".to_string(), Function::Reverse);
+    state.add_function("nreverse".to_string(), Function::Nreverse);
+    state.add_function("member".to_string(), Function::Member);
+    state.add_function("copy-list".to_string(), Function::CopyList);
+    state.add_function("null".to_string(), Function::Null);
+    state.add_function("endp".to_string(), Function::Endp);
+    state.add_function("function".to_string(), Function::SharpQuote);
+    state.add_function("funcall".to_string(), Function::Funcall);
+    state.add_function("apply".to_string(), Function::Apply);
+    state.add_function("defmacro".to_string(), Function::Defmacro);
+    state.add_function("macroexpand-1".to_string(), Function::Macroexpand1);
+    state.add_function("macroexpand".to_string(), Function::Macroexpand);
+    state.add_function("gensym".to_string(), Function::Gensym);
+    state.add_function("quote".to_string(), Function::Quote);
+    state.add_function("eval".to_string(), Function::Eval);
+    state.add_function("values".to_string(), Function::Values);
+    state.add_function("multiple-value-bind".to_string(), Function::MultipleValueBind);
+    state.add_function("multiple-value-list".to_string(), Function::MultipleValueList);
+    state.add_function("multiple-value-call".to_string(), Function::MultipleValueCall);
+    state.add_function("nth-value".to_string(), Function::NthValue);
+    state.add_function("documentation".to_string(), Function::Documentation);
+    state.add_function("describe".to_string(), Function::Describe);
 }
 
-pub fn run(code: &str) {
-    let mut state = State::new();
-    initialize_functions(&mut state);
+fn initialize_variables(state: &mut State) {
+    let random_state = random::random_state_value(RandomState::fresh());
+    let variables = [
+        (random::RANDOM_STATE, random_state),
+        (conversion::PRINT_BASE, LispValue::Int(10)),
+        (conversion::PRINT_RADIX, LispValue::nil()),
+        (conversion::READ_BASE, LispValue::Int(10)),
+        (
+            conversion::READ_DEFAULT_FLOAT_FORMAT,
+            LispValue::Name("double-float".to_string()),
+        ),
+    ];
+    for (name, value) in variables {
+        // Names are never constants at startup, so defining them cannot fail.
+        let _ = state.define_special(name.to_string(), Some(value), true);
+    }
+}
+
+/// Stack reserved for each level of nested calls on the evaluation thread,
+/// with room to spare over what debug builds use, so deep recursion ends
+/// with a Lisp error instead of a Rust stack overflow.
+const STACK_PER_CALL: usize = 64 * 1024;
+
+/// Stack for the evaluation around the nested calls.
+const BASE_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// A value handed between the caller of `Interpreter::evaluate` and the
+/// thread that runs the evaluation.
+struct Handoff<T>(T);
+
+// SAFETY: the state of an interpreter is made of `Rc`s, which must not be
+// used from two threads at once. A handoff only crosses into a scoped thread
+// while the calling thread waits for it to finish, and back once it has, so
+// the values inside are only ever used by one thread at a time.
+unsafe impl<T> Send for Handoff<T> {}
+
+impl<T> Handoff<T> {
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// Interpreter state that can be kept between runs. Errors unwind back to
+/// the caller, so the same interpreter can keep running code afterwards.
+pub struct Interpreter {
+    state: State,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let mut state = State::new();
+        initialize_functions(&mut state);
+        initialize_variables(&mut state);
+        Self { state }
+    }
 
-    if let Some(functions) = parse(code) {
-        for f in functions {
-            if let LispValue::Function(mut arguments) = f {
-                if let LispValue::Name(name) = arguments.remove(0) {
-                    execute_function(&mut state, name.as_str(), arguments);
-                }
+    /// Replaces `*random-state*` with a generator seeded with `seed`, so the
+    /// same seed always gives the same random numbers.
+    pub fn set_random_seed(&mut self, seed: u64) {
+        let random_state = random::random_state_value(RandomState::from_seed(seed));
+        let _ = self
+            .state
+            .set_variable(random::RANDOM_STATE.to_string(), random_state);
+    }
+
+    /// Sets how many nested function calls are allowed before a control
+    /// stack exhausted error is raised. Evaluation runs on a thread with
+    /// 64 KiB of stack for each level, so the limit does not depend on the
+    /// stack of the calling thread.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.state.set_max_depth(max_depth);
+    }
+
+    /// Makes `true` and `false` evaluate to booleans and predicates return
+    /// them, as in older lisper code. Off by default, where `nil` is the only
+    /// false value and `t` the canonical true one.
+    pub fn set_boolean_compatibility(&mut self, enabled: bool) {
+        self.state.set_boolean_compatibility(enabled);
+    }
+
+    /// Evaluates every form of `code` and returns the value of the last one.
+    /// Each form is read after the ones before it are evaluated, so it sees
+    /// the `*read-base*` and `*read-default-float-format*` they leave.
+    ///
+    /// The forms run on a thread with a stack large enough for the call
+    /// depth limit. When no such stack can be reserved the evaluation fails
+    /// with a control stack exhausted error.
+    pub fn evaluate(&mut self, code: &str) -> LispResult {
+        let max_depth = self.state.max_depth();
+        let stack_size = match max_depth
+            .checked_mul(STACK_PER_CALL)
+            .and_then(|size| size.checked_add(BASE_STACK_SIZE))
+        {
+            Some(size) => size,
+            None => return Err(LispError::StackExhausted(max_depth)),
+        };
+
+        let interpreter = Handoff(self);
+        thread::scope(|scope| {
+            let evaluation = thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn_scoped(scope, move || {
+                    Handoff(interpreter.into_inner().evaluate_forms(code))
+                });
+            match evaluation {
+                Ok(evaluation) => match evaluation.join() {
+                    Ok(result) => result.into_inner(),
+                    Err(payload) => panic::resume_unwind(payload),
+                },
+                Err(_) => Err(LispError::StackExhausted(max_depth)),
             }
+        })
+    }
+
+    fn evaluate_forms(&mut self, code: &str) -> LispResult {
+        let mut result = LispValue::nil();
+        let mut rest = code;
+        loop {
+            let read_options = conversion::read_options(&self.state)?;
+            let (form, end) =
+                match read_from_string(rest, read_options).map_err(LispError::Parse)? {
+                    Some(read) => read,
+                    None => return Ok(result),
+                };
+            // The reader counts characters, the rest of the code is sliced
+            // by bytes.
+            let end = rest
+                .char_indices()
+                .nth(end)
+                .map_or(rest.len(), |(index, _)| index);
+            rest = &rest[end..];
+            result = evaluate_values(&mut self.state, form)?;
+        }
+    }
+
+    pub fn run(&mut self, code: &str) {
+        if let Err(error) = self.evaluate(code) {
+            println!("Error: {}", error);
         }
     }
 }
+
+#[cfg(test)]
+mod interpreter_test {
+
+    use super::*;
+
+    fn run_code(code: &str) -> LispValue {
+        Interpreter::new()
+            .evaluate(code)
+            .expect("Evaluation failed")
+    }
+
+    #[test]
+    fn top_level_forms() {
+        let name = |s: &str| LispValue::Name(s.to_string());
+        assert_eq!(
+            run_code("'(foo bar)"),
+            LispValue::Function(vec![name("foo"), name("bar")])
+        );
+        assert_eq!(run_code("(defvar x 5) x"), LispValue::Int(5));
+        assert_eq!(run_code("(+ 1 2) 10"), LispValue::Int(10));
+        assert_eq!(run_code("\"s\""), LispValue::String("s".to_string()));
+        assert_eq!(run_code("'x ; comment"), name("x"));
+        assert_eq!(run_code("#C(1 2)"), run_code("(complex 1 2)"));
+        assert_eq!(run_code("1(+ 1 1)"), LispValue::Int(2));
+        assert_eq!(run_code("(defvar ä 1)(+ ä 1)"), LispValue::Int(2));
+        assert_eq!(run_code(""), LispValue::nil());
+
+        let mut interpreter = Interpreter::new();
+        let parse_error = |message: &str| Err(LispError::Parse(message.to_string()));
+        assert_eq!(
+            interpreter.evaluate("(list 1 ')"),
+            parse_error("unmatched close parenthesis")
+        );
+        assert_eq!(
+            interpreter.evaluate("(+ 1 2"),
+            parse_error("end of file in list")
+        );
+        assert_eq!(
+            interpreter.evaluate("(+ 1 2))"),
+            parse_error("unmatched close parenthesis")
+        );
+        assert_eq!(
+            interpreter.evaluate("(defvar y 1) (setq y 2) (+ y"),
+            parse_error("end of file in list")
+        );
+        assert_eq!(interpreter.evaluate("y"), Ok(LispValue::Int(2)));
+    }
+
+    #[test]
+    fn lambda() {
+        assert_eq!(run_code("((lambda (x y) (+ x y)) 3 4)"), LispValue::Int(7));
+        assert_eq!(
+            run_code("(funcall (lambda (x) (* x x)) 5)"),
+            LispValue::Int(25)
+        );
+    }
+
+    #[test]
+    fn function_values() {
+        assert_eq!(run_code("(funcall #'+ 1 2 3)"), LispValue::Int(6));
+        assert_eq!(run_code("(funcall (function -) 10 4)"), LispValue::Int(6));
+        assert_eq!(
+            run_code("(defun square (n) (* n n)) (funcall #'square 6)"),
+            LispValue::Int(36)
+        );
+        assert_eq!(
+            run_code("(defvar f #'(lambda (x) (+ x 1))) (funcall f 1)"),
+            LispValue::Int(2)
+        );
+        assert_eq!(
+            run_code("(defun square (n) (* n n))"),
+            LispValue::Name("square".to_string())
+        );
+        assert_eq!(run_code("(defun f ())"), LispValue::Name("f".to_string()));
+        assert_eq!(run_code("(defun f ()) (f)"), LispValue::nil());
+        assert_eq!(
+            run_code("(defun f (x) \"Does nothing.\") (f 1)"),
+            LispValue::String("Does nothing.".to_string())
+        );
+    }
+
+    #[test]
+    fn apply() {
+        assert_eq!(
+            run_code("(defun twice (f x) (funcall f (funcall f x))) (apply #'twice #'(lambda (n) (* n 3)) 2 ())"),
+            LispValue::Int(18)
+        );
+        assert_eq!(run_code("(funcall 'car '(1 2))"), LispValue::Int(1));
+        assert_eq!(run_code("(apply 'list 1 '(2))"), run_code("(list 1 2)"));
+        assert_eq!(
+            run_code("(defun f () (funcall 'cdr '(1 2))) (f)"),
+            run_code("(list 2)")
+        );
+    }
+
+    #[test]
+    fn undefined_functions() {
+        let mut interpreter = Interpreter::new();
+        interpreter.evaluate("(defmacro m (x) x)").unwrap();
+        let undefined = |name: &str| Err(LispError::UndefinedFunction(name.to_string()));
+
+        assert_eq!(interpreter.evaluate("(foo 1 2)"), undefined("foo"));
+        assert_eq!(
+            interpreter.evaluate("(defun g () (foo 1)) (g)"),
+            undefined("foo")
+        );
+        assert_eq!(
+            interpreter.evaluate("(funcall #'nosuch 1)"),
+            undefined("nosuch")
+        );
+        assert_eq!(
+            interpreter.evaluate("(function nosuch)"),
+            undefined("nosuch")
+        );
+        assert_eq!(
+            interpreter.evaluate("(funcall 'nosuch 1)"),
+            undefined("nosuch")
+        );
+        assert_eq!(interpreter.evaluate("(funcall 'm 1)"), undefined("m"));
+        assert_eq!(interpreter.evaluate("(funcall 'if t 1 2)"), undefined("if"));
+        assert_eq!(
+            interpreter.evaluate("(funcall 5 1)"),
+            Err(LispError::TypeError {
+                function: "funcall".to_string(),
+                expected: "function".to_string(),
+                value: LispValue::Int(5),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(apply #'+ 1 2)"),
+            Err(LispError::TypeError {
+                function: "apply".to_string(),
+                expected: "list".to_string(),
+                value: LispValue::Int(2),
+            })
+        );
+
+        let illegal = |code: &str| {
+            Err(LispError::IllegalFunctionCall(run_code(&format!(
+                "'{}",
+                code
+            ))))
+        };
+        assert_eq!(interpreter.evaluate("(1 2 3)"), illegal("(1 2 3)"));
+        assert_eq!(interpreter.evaluate("((foo) 1)"), illegal("((foo) 1)"));
+        assert_eq!(
+            interpreter.evaluate("((lambda) 1)"),
+            illegal("((lambda) 1)")
+        );
+        assert_eq!(
+            interpreter.evaluate("(defun h () (\"f\" 1)) (h)"),
+            illegal("(\"f\" 1)")
+        );
+        assert_eq!(
+            interpreter.evaluate("((lambda (x) (* x 2)) 4)"),
+            Ok(LispValue::Int(8))
+        );
+    }
+
+    #[test]
+    fn closures() {
+        let code = r#"
+            (defun make-adder (n) (lambda (x) (+ x n)))
+            (defvar add5 (make-adder 5))
+            (defvar add10 (make-adder 10))
+            (+ (funcall add5 1) (funcall add10 1))
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(17));
+    }
+
+    #[test]
+    fn let_scopes() {
+        assert_eq!(run_code("(let ((x 2) (y 3)) (* x y))"), LispValue::Int(6));
+        assert_eq!(
+            run_code("(defvar x 1) (let ((x 2) (y x)) y)"),
+            LispValue::Int(1)
+        );
+        assert_eq!(
+            run_code("(defvar x 1) (let* ((x 2) (y x)) y)"),
+            LispValue::Int(2)
+        );
+        assert_eq!(
+            run_code("(defvar x 1) (let ((x 2)) x) (+ x 0)"),
+            LispValue::Int(1)
+        );
+    }
+
+    #[test]
+    fn setq_outer_scope() {
+        assert_eq!(run_code("(setq x 10) (+ x 0)"), LispValue::Int(10));
+        assert_eq!(
+            run_code("(defvar x 1) (let ((y 2)) (setq x y)) (+ x 0)"),
+            LispValue::Int(2)
+        );
+
+        let code = r#"
+            (defun make-counter ()
+                (let ((count 0))
+                    (lambda () (setq count (+ count 1)))))
+            (defvar counter (make-counter))
+            (funcall counter)
+            (funcall counter)
+            (funcall counter)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(3));
+    }
+
+    #[test]
+    fn shared_closure_environment() {
+        let code = r#"
+            (defvar get 0)
+            (defvar increment
+                (let ((n 0))
+                    (setq get (lambda () n))
+                    (lambda () (setq n (+ n 1)))))
+            (funcall increment)
+            (funcall increment)
+            (funcall get)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(2));
+    }
+
+    #[test]
+    fn if_block() {
+        assert_eq!(run_code("(if (< 1 2) 1 2)"), LispValue::Int(1));
+        assert_eq!(run_code("(if (> 1 2) 1 2)"), LispValue::Int(2));
+        assert_eq!(run_code("(if (> 1 2) 1)"), LispValue::nil());
+        assert_eq!(
+            run_code("(progn (defvar x 1) (setq x (+ x 1)) x)"),
+            LispValue::Int(2)
+        );
+    }
+
+    #[test]
+    fn tail_calls() {
+        let code = r#"
+            (defun sum-to (n acc)
+                (if (= n 0)
+                    acc
+                    (sum-to (- n 1) (+ acc n))))
+            (sum-to 1000000 0)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(500000500000));
+
+        let code = r#"
+            (defun count-down (n)
+                (let ((next (- n 1)))
+                    (progn (if (= n 0) 0 (funcall #'count-down next)))))
+            (count-down 100000)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(0));
+    }
+
+    #[test]
+    fn mutual_tail_calls() {
+        let code = r#"
+            (defun is-even (n) (if (= n 0) t (is-odd (- n 1))))
+            (defun is-odd (n) (if (= n 0) nil (is-even (- n 1))))
+            (is-even 100001)
+        "#;
+        assert_eq!(run_code(code), LispValue::nil());
+    }
+
+    #[test]
+    fn stack_exhausted() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_depth(20);
+        interpreter
+            .evaluate("(defun deep (n) (if (= n 0) 0 (+ 1 (deep (- n 1)))))")
+            .unwrap();
+
+        assert_eq!(interpreter.evaluate("(deep 10)"), Ok(LispValue::Int(10)));
+        assert_eq!(
+            interpreter.evaluate("(deep 50)"),
+            Err(LispError::StackExhausted(20))
+        );
+        assert_eq!(interpreter.evaluate("(deep 19)"), Ok(LispValue::Int(19)));
+
+        interpreter.set_max_depth(40);
+        assert_eq!(interpreter.evaluate("(deep 30)"), Ok(LispValue::Int(30)));
+    }
+
+    #[test]
+    fn default_depth_fits_default_stack() {
+        let result = std::thread::spawn(|| {
+            let mut interpreter = Interpreter::new();
+            interpreter
+                .evaluate(
+                    "(defun fact (n) (if (< n 2) 1 (* n (fact (- n 1)))))
+                     (defun deep (n)
+                       (let ((m (- n 1)))
+                         (if (= n 0) 0 (+ 1 (apply #'funcall (list #'deep m))))))",
+                )
+                .unwrap();
+            let fact = interpreter
+                .evaluate("(fact 100)")
+                .map(|value| value.to_string().len())
+                == Ok(158);
+            let deep = interpreter.evaluate("(deep 2000)") == Ok(LispValue::Int(2000));
+            let exhausted = interpreter.evaluate("(deep 100000)")
+                == Err(LispError::StackExhausted(DEFAULT_MAX_DEPTH));
+            (fact, deep, exhausted)
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(result, (true, true, true));
+    }
+
+    #[test]
+    fn dotimes() {
+        assert_eq!(
+            run_code("(let ((sum 0)) (dotimes (i 5 sum) (setq sum (+ sum i))))"),
+            LispValue::Int(10)
+        );
+        assert_eq!(run_code("(dotimes (i 3 i))"), LispValue::Int(3));
+        assert_eq!(
+            run_code("(dotimes (i 10) (if (= i 3) (return i)))"),
+            LispValue::Int(3)
+        );
+
+        let code = r#"
+            (defvar first 0)
+            (defvar second 0)
+            (dotimes (i 2)
+                (if (= i 0)
+                    (setq first (lambda () i))
+                    (setq second (lambda () i))))
+            (+ (* 10 (funcall first)) (funcall second))
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(1));
+
+        assert_eq!(
+            run_code("(dotimes (i 100000000000000000000) (return i))"),
+            LispValue::Int(0)
+        );
+        assert_eq!(
+            run_code("(dotimes (i -100000000000000000000 i))"),
+            LispValue::Int(0)
+        );
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(dotimes (i \"3\") i)"),
+            Err(LispError::TypeError {
+                function: "dotimes".to_string(),
+                expected: "integer".to_string(),
+                value: LispValue::String("3".to_string()),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(dolist (x 5) x)"),
+            Err(LispError::TypeError {
+                function: "dolist".to_string(),
+                expected: "list".to_string(),
+                value: LispValue::Int(5),
+            })
+        );
+    }
+
+    #[test]
+    fn do_loops() {
+        assert_eq!(
+            run_code("(do ((i 0 (+ i 1)) (acc 0 (+ acc i))) ((= i 5) acc))"),
+            LispValue::Int(10)
+        );
+        assert_eq!(
+            run_code("(do ((i 0 (+ i 1)) (j 0 i)) ((= i 3) j))"),
+            LispValue::Int(2)
+        );
+        assert_eq!(
+            run_code("(do* ((i 0 (+ i 1)) (j i i)) ((= i 3) j))"),
+            LispValue::Int(3)
+        );
+        assert_eq!(
+            run_code("(do ((i 0 (+ i 1))) ((= i 100) i) (if (= i 7) (return (* i 2))))"),
+            LispValue::Int(14)
+        );
+    }
+
+    #[test]
+    fn loop_and_while() {
+        assert_eq!(
+            run_code("(let ((n 0)) (loop (setq n (+ n 1)) (if (= n 10) (return n))))"),
+            LispValue::Int(10)
+        );
+        assert_eq!(
+            run_code("(let ((n 0)) (while (< n 5) (setq n (+ n 1))) (+ n 0))"),
+            LispValue::Int(5)
+        );
+        assert_eq!(
+            run_code("(let ((n 0)) (while t (setq n (+ n 1)) (if (> n 3) (return n))))"),
+            LispValue::Int(4)
+        );
+        assert_eq!(run_code("(dolist (x () 5))"), LispValue::Int(5));
+        assert_eq!(
+            run_code("(multiple-value-list (loop (return (values 1 2))))"),
+            int_list(&[1, 2])
+        );
+        assert_eq!(
+            run_code("(multiple-value-list (dotimes (i 5) (if (= i 2) (return (values i 7)))))"),
+            int_list(&[2, 7])
+        );
+        assert_eq!(
+            Interpreter::new().evaluate("(return 1)"),
+            Err(LispError::ReturnOutsideLoop)
+        );
+    }
+
+    #[test]
+    fn lexical_return() {
+        let code = r#"
+            (defun g () (return 5))
+            (defun outer () (dotimes (i 3) (g)) 'done)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.evaluate(code).unwrap();
+        assert_eq!(
+            interpreter.evaluate("(outer)"),
+            Err(LispError::ReturnOutsideLoop)
+        );
+        assert_eq!(
+            interpreter.evaluate("(dotimes (i 3) (g))"),
+            Err(LispError::ReturnOutsideLoop)
+        );
+
+        let code = r#"
+            (defun find-even (list)
+              (dolist (x list) (funcall (lambda (y) (if (= (mod y 2) 0) (return y))) x)))
+            (find-even '(1 3 4 5 6))
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(4));
+
+        let code = r#"
+            (defun inner () (dotimes (i 10) (if (= i 3) (return i))))
+            (dotimes (j 5) (if (= (inner) 3) (return (list j (inner)))))
+        "#;
+        assert_eq!(run_code(code), int_list(&[0, 3]));
+
+        let code = r#"
+            (defvar escape (dotimes (i 1) (return (lambda () (return 1)))))
+            (funcall escape)
+        "#;
+        assert!(matches!(
+            Interpreter::new().evaluate(code),
+            Err(LispError::Return(..))
+        ));
+    }
+
+    fn int_list(values: &[i64]) -> LispValue {
+        LispValue::Function(values.iter().map(|i| LispValue::Int(*i)).collect())
+    }
+
+    #[test]
+    fn list_construction() {
+        assert_eq!(run_code("(list 1 2 3)"), int_list(&[1, 2, 3]));
+        assert_eq!(run_code("(cons 1 (list 2 3))"), int_list(&[1, 2, 3]));
+        assert_eq!(run_code("(list* 1 2 (list 3))"), int_list(&[1, 2, 3]));
+        assert_eq!(
+            run_code("(append (list 1) () (list 2 3))"),
+            int_list(&[1, 2, 3])
+        );
+        assert_eq!(run_code("(copy-list (list 1 2))"), int_list(&[1, 2]));
+        assert_eq!(
+            run_code("(cons 1 2)"),
+            LispValue::DottedList(vec![LispValue::Int(1)], Box::new(LispValue::Int(2)))
+        );
+        assert_eq!(run_code("(cdr (cons 1 2))"), LispValue::Int(2));
+        assert_eq!(run_code("(cdr (list* 1 2 3))"), run_code("(cons 2 3)"));
+    }
+
+    #[test]
+    fn list_access() {
+        assert_eq!(run_code("(car (list 1 2 3))"), LispValue::Int(1));
+        assert_eq!(run_code("(cdr (list 1 2 3))"), int_list(&[2, 3]));
+        assert_eq!(run_code("(first (list 1 2 3))"), LispValue::Int(1));
+        assert_eq!(run_code("(rest (list 1))"), int_list(&[]));
+        assert_eq!(run_code("(car nil)"), LispValue::nil());
+        assert_eq!(run_code("(nth 2 (list 1 2 3))"), LispValue::Int(3));
+        assert_eq!(run_code("(nth 5 (list 1 2 3))"), int_list(&[]));
+        assert_eq!(run_code("(nthcdr 1 (list 1 2 3))"), int_list(&[2, 3]));
+        assert_eq!(run_code("(last (list 1 2 3))"), int_list(&[3]));
+        assert_eq!(run_code("(last (list 1 2 3) 2)"), int_list(&[2, 3]));
+        assert_eq!(run_code("(butlast (list 1 2 3))"), int_list(&[1, 2]));
+        assert_eq!(run_code("(length (list 1 2 3))"), LispValue::Int(3));
+        assert_eq!(run_code("(reverse (list 1 2 3))"), int_list(&[3, 2, 1]));
+        assert_eq!(run_code("(nreverse (list 1 2))"), int_list(&[2, 1]));
+        assert_eq!(run_code("(member 2 (list 1 2 3))"), int_list(&[2, 3]));
+        assert_eq!(run_code("(member 4 (list 1 2 3))"), int_list(&[]));
+        assert_eq!(run_code("(null ())"), LispValue::t());
+        assert_eq!(run_code("(endp (list 1))"), LispValue::nil());
+        assert_eq!(
+            run_code("(let ((sum 0)) (dolist (x (list 1 2 3) sum) (setq sum (+ sum x))))"),
+            LispValue::Int(6)
+        );
+    }
+
+    #[test]
+    fn list_type_errors() {
+        let error = |function: &str, value| {
+            Err(LispError::TypeError {
+                function: function.to_string(),
+                expected: "list".to_string(),
+                value,
+            })
+        };
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(car 1)"),
+            error("car", LispValue::Int(1))
+        );
+        assert_eq!(
+            interpreter.evaluate("(rest 1.5)"),
+            error("rest", LispValue::Float(1.5))
+        );
+        assert_eq!(
+            interpreter.evaluate("(append 1 (list 2))"),
+            error("append", LispValue::Int(1))
+        );
+        assert_eq!(
+            interpreter.evaluate("(reverse (cons 1 2))"),
+            error(
+                "reverse",
+                LispValue::DottedList(vec![LispValue::Int(1)], Box::new(LispValue::Int(2)))
+            )
+        );
+        assert_eq!(
+            interpreter.evaluate("(endp 3)"),
+            error("endp", LispValue::Int(3))
+        );
+    }
+
+    #[test]
+    fn generalized_booleans() {
+        assert_eq!(run_code("(< 1 2)"), LispValue::t());
+        assert_eq!(run_code("(= 1 2)"), LispValue::nil());
+        assert_eq!(run_code("(if 0 1 2)"), LispValue::Int(1));
+        assert_eq!(run_code("(if (list) 1 2)"), LispValue::Int(2));
+        assert_eq!(run_code("(if nil 1 2)"), LispValue::Int(2));
+        assert_eq!(run_code("(if \"\" 1 2)"), LispValue::Int(1));
+        assert_eq!(run_code("(not nil)"), LispValue::t());
+        assert_eq!(run_code("(not 5)"), LispValue::nil());
+        assert_eq!(run_code("(and 1 (list 2) t)"), LispValue::t());
+        assert_eq!(run_code("(or nil (> 1 2))"), LispValue::nil());
+        assert_eq!(run_code("(if 'false 1 2)"), LispValue::Int(1));
+    }
+
+    #[test]
+    fn boolean_compatibility() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_boolean_compatibility(true);
+        assert_eq!(
+            interpreter.evaluate("(< 1 2)"),
+            Ok(LispValue::Boolean(true))
+        );
+        assert_eq!(
+            interpreter.evaluate("(if false 1 2)"),
+            Ok(LispValue::Int(2))
+        );
+        assert_eq!(interpreter.evaluate("(if nil 1 2)"), Ok(LispValue::Int(2)));
+        assert_eq!(
+            interpreter.evaluate("(and true (= 1 1))"),
+            Ok(LispValue::Boolean(true))
+        );
+        assert_eq!(
+            interpreter.evaluate("(not false)"),
+            Ok(LispValue::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn short_circuit() {
+        assert_eq!(run_code("(and)"), LispValue::t());
+        assert_eq!(run_code("(or)"), LispValue::nil());
+        assert_eq!(run_code("(and 1 2 3)"), LispValue::Int(3));
+        assert_eq!(run_code("(and 1 nil 3)"), LispValue::nil());
+        assert_eq!(run_code("(or nil 2 3)"), LispValue::Int(2));
+        assert_eq!(
+            run_code("(or nil \"a\" 1.5)"),
+            LispValue::String("a".to_string())
+        );
+        assert_eq!(run_code("(or (< 2 1) (list 1))"), int_list(&[1]));
+
+        let code = r#"
+            (defvar calls 0)
+            (defun touch (value) (setq calls (+ calls 1)) value)
+            (and (touch nil) (touch 1) (touch 2))
+            (or (touch 1) (touch 2))
+            (+ calls 0)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(2));
+
+        let code = r#"
+            (defun count-down (n) (or (= n 0) (count-down (- n 1))))
+            (count-down 100000)
+        "#;
+        assert_eq!(run_code(code), LispValue::t());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_boolean_compatibility(true);
+        assert_eq!(
+            interpreter.evaluate("(and 1 false 2)"),
+            Ok(LispValue::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn macros() {
+        let code = r#"
+            (defmacro my-unless (test &body body) (list 'if test nil (cons 'progn body)))
+            (defvar x 1)
+            (my-unless t (setq x 5))
+            (my-unless (> 1 2) (setq x (+ x 1)) (+ x 10))
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(12));
+
+        let code = r#"
+            (defmacro with-pair ((a b) . body) (list* 'let (list (list a 1) (list b 2)) body))
+            (with-pair (x y) (+ x y))
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(3));
+
+        let code = r#"
+            (defmacro incr (place &optional (delta 1 delta-p)) (list 'setq place (list '+ place delta)))
+            (defvar n 0)
+            (incr n)
+            (incr n 10)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(11));
+    }
+
+    #[test]
+    fn macro_tail_calls() {
+        let code = r#"
+            (defmacro my-if (test then else) (list 'if test then else))
+            (defun count-down (n) (my-if (= n 0) 0 (count-down (- n 1))))
+            (count-down 1000)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_depth(50);
+        assert_eq!(interpreter.evaluate(code), Ok(LispValue::Int(0)));
+    }
+
+    #[test]
+    fn macroexpand() {
+        let name = |name: &str| LispValue::Name(name.to_string());
+        let setq = LispValue::Function(vec![
+            name("setq"),
+            name("counter"),
+            LispValue::Function(vec![name("+"), name("counter"), LispValue::Int(1)]),
+        ]);
+        let macros = r#"
+            (defmacro incr (place) (list 'setq place (list '+ place 1)))
+            (defmacro incr-once (place) (list 'incr place))
+        "#;
+
+        let code = format!("{} (macroexpand-1 '(incr-once counter))", macros);
+        assert_eq!(
+            run_code(&code),
+            LispValue::Function(vec![name("incr"), name("counter")])
+        );
+        let code = format!("{} (macroexpand '(incr-once counter))", macros);
+        assert_eq!(run_code(&code), setq);
+        let code = format!("{} (macroexpand '(+ 1 2))", macros);
+        assert_eq!(
+            run_code(&code),
+            LispValue::Function(vec![name("+"), LispValue::Int(1), LispValue::Int(2)])
+        );
+    }
+
+    #[test]
+    fn gensym() {
+        let mut interpreter = Interpreter::new();
+        let first = interpreter.evaluate("(gensym)").unwrap();
+        let second = interpreter.evaluate("(gensym \"TEMP\")").unwrap();
+        assert_eq!(first, LispValue::Name("#:G1".to_string()));
+        assert_eq!(second, LispValue::Name("#:TEMP2".to_string()));
+    }
+
+    #[test]
+    fn quote_and_eval() {
+        let name = |name: &str| LispValue::Name(name.to_string());
+        let sum = LispValue::Function(vec![name("+"), LispValue::Int(1), LispValue::Int(2)]);
+        assert_eq!(run_code("(quote (+ 1 2))"), sum);
+        assert_eq!(run_code("(car '(+ 1 2))"), name("+"));
+        assert_eq!(
+            run_code("(list 'a ''b)"),
+            LispValue::Function(vec![
+                name("a"),
+                LispValue::Function(vec![name("quote"), name("b")]),
+            ])
+        );
+        assert_eq!(run_code("(eval '(+ 1 2))"), LispValue::Int(3));
+        assert_eq!(run_code("(eval (list '* 2 (+ 1 2)))"), LispValue::Int(6));
+        assert_eq!(
+            run_code("(list :key t)"),
+            LispValue::Function(vec![name(":key"), name("t")])
+        );
+
+        let code = r#"
+            (setq x 1)
+            (let ((x 2)) (eval 'x))
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(1));
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(list 'a b)"),
+            Err(LispError::UnboundVariable("b".to_string()))
+        );
+    }
+
+    #[test]
+    fn lambda_lists() {
+        let code = r#"
+            (defun opt (a &optional (b (* a 2) b-p) c) (list a b b-p c))
+            (list (opt 1) (opt 1 5 6))
+        "#;
+        let expected = run_code("(quote ((1 2 nil nil) (1 5 t 6)))");
+        assert_eq!(run_code(code), expected);
+
+        let code = "(funcall (lambda (a &rest more) (cons a more)) 1 2 3)";
+        assert_eq!(run_code(code), int_list(&[1, 2, 3]));
+
+        let code = r#"
+            (defun keys (&key (width 10) height ((:depth d) 1 d-p)) (list width height d d-p))
+            (list (keys) (keys :height 2 :depth 3 :height 4))
+        "#;
+        let expected = run_code("(quote ((10 nil 1 nil) (10 2 3 t)))");
+        assert_eq!(run_code(code), expected);
+
+        let code = r#"
+            (defun loose (&key a &allow-other-keys) a)
+            (defun strict (&key a) a)
+            (+ (loose :b 1 :a 2) (strict :a 3 :b 4 :allow-other-keys t))
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(5));
+
+        let code = "(funcall #'(lambda (a &aux (b (+ a 1)) c) (list a b c)) 1)";
+        assert_eq!(run_code(code), run_code("(quote (1 2 nil))"));
+    }
+
+    #[test]
+    fn arity_errors() {
+        let code = r#"
+            (defun pair (a b) (list a b))
+            (defun range (a &optional b) a)
+            (defun at-least (a &rest b) a)
+            (defun keys (&key a) a)
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.evaluate(code).unwrap();
+
+        let count = |expected: &str, given| LispError::ArgumentCount {
+            expected: expected.to_string(),
+            given,
+        };
+        assert_eq!(interpreter.evaluate("(pair 1)"), Err(count("2", 1)));
+        assert_eq!(
+            interpreter.evaluate("(range 1 2 3)"),
+            Err(count("1 to 2", 3))
+        );
+        assert_eq!(
+            interpreter.evaluate("(at-least)"),
+            Err(count("at least 1", 0))
+        );
+        assert_eq!(
+            interpreter.evaluate("(keys :a)"),
+            Err(count("an even number of keyword arguments", 1))
+        );
+        assert_eq!(
+            interpreter.evaluate("(keys :b 1)"),
+            Err(LispError::UnknownKeyword(LispValue::Name(":b".to_string())))
+        );
+
+        assert_eq!(interpreter.evaluate("(if)"), Err(count("2 to 3", 0)));
+        assert_eq!(
+            interpreter.evaluate("(if t 1 2 3)"),
+            Err(count("2 to 3", 4))
+        );
+        assert_eq!(
+            interpreter.evaluate("(setq x)"),
+            Err(count("an even number of arguments", 1))
+        );
+        assert_eq!(interpreter.evaluate("(quote a b)"), Err(count("1", 2)));
+        assert_eq!(
+            interpreter.evaluate("(funcall)"),
+            Err(count("at least 1", 0))
+        );
+        assert_eq!(
+            interpreter.evaluate("(apply #'+)"),
+            Err(count("at least 2", 1))
+        );
+        assert_eq!(
+            interpreter.evaluate("(defun f () (funcall)) (f)"),
+            Err(count("at least 1", 0))
+        );
+        assert_eq!(
+            interpreter.evaluate("(multiple-value-call)"),
+            Err(count("at least 1", 0))
+        );
+        assert_eq!(interpreter.evaluate("(cons 1)"), Err(count("2", 1)));
+        assert_eq!(interpreter.evaluate("(car)"), Err(count("1", 0)));
+        assert_eq!(interpreter.evaluate("(cdr '(1) '(2))"), Err(count("1", 2)));
+        assert_eq!(interpreter.evaluate("(nth 1)"), Err(count("2", 1)));
+        assert_eq!(interpreter.evaluate("(length)"), Err(count("1", 0)));
+        assert_eq!(
+            interpreter.evaluate("(setq 1 2)"),
+            Err(type_error("setq", "symbol", LispValue::Int(1)))
+        );
+        assert_eq!(
+            format!("{}", count("1 to 2", 3)),
+            "Invalid number of arguments: 3, expected 1 to 2"
+        );
+    }
+
+    #[test]
+    fn multiple_values() {
+        let values =
+            |values: &[i64]| LispValue::Values(values.iter().map(|&i| LispValue::Int(i)).collect());
+        assert_eq!(run_code("(values 1 2 3)"), values(&[1, 2, 3]));
+        assert_eq!(run_code("(values)"), values(&[]));
+        assert_eq!(run_code("(values 1)"), LispValue::Int(1));
+        assert_eq!(run_code("(+ (values 1 2) 10)"), LispValue::Int(11));
+        assert_eq!(
+            run_code("(list (values) (values 1 2))"),
+            run_code("(list nil 1)")
+        );
+        assert_eq!(
+            run_code("(multiple-value-list (values 1 2))"),
+            int_list(&[1, 2])
+        );
+        assert_eq!(run_code("(multiple-value-list 5)"), int_list(&[5]));
+        assert_eq!(
+            run_code("(multiple-value-call #'list (values 1 2) 3 (values))"),
+            int_list(&[1, 2, 3])
+        );
+        assert_eq!(
+            run_code("(multiple-value-call 'list (values 1 2))"),
+            int_list(&[1, 2])
+        );
+        assert_eq!(
+            Interpreter::new().evaluate("(multiple-value-call 'if t 1)"),
+            Err(LispError::UndefinedFunction("if".to_string()))
+        );
+        assert_eq!(
+            Interpreter::new().evaluate("(multiple-value-call 5 1)"),
+            Err(LispError::TypeError {
+                function: "multiple-value-call".to_string(),
+                expected: "function".to_string(),
+                value: LispValue::Int(5),
+            })
+        );
+        assert_eq!(run_code("(nth-value 1 (values 1 2))"), LispValue::Int(2));
+        assert_eq!(run_code("(nth-value 2 (values 1 2))"), LispValue::nil());
+        assert_eq!(
+            Interpreter::new().evaluate("(nth-value -1 (values 1 2))"),
+            Err(type_error(
+                "nth-value",
+                "unsigned integer",
+                LispValue::Int(-1)
+            ))
+        );
+
+        let code = r#"
+            (defun split (n) (if (> n 0) (values n (- 0 n)) (values 0 0)))
+            (defun pass (n) (let ((m n)) (split m)))
+            (multiple-value-bind (a b c) (pass 3) (list a b c))
+        "#;
+        assert_eq!(run_code(code), run_code("(list 3 -3 nil)"));
+
+        let code = r#"
+            (defvar x (values 1 2))
+            (multiple-value-list x)
+        "#;
+        assert_eq!(run_code(code), int_list(&[1]));
+    }
+
+    #[test]
+    fn special_variables() {
+        let code = r#"
+            (defvar *count* 1)
+            (defvar *count* 2)
+            (defparameter *limit* 1)
+            (defparameter *limit* 2)
+            (list *count* *limit*)
+        "#;
+        assert_eq!(run_code(code), int_list(&[1, 2]));
+
+        let code = r#"
+            (defun define () (defvar *global* 5))
+            (define)
+            (+ *global* 0)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(5));
+
+        let code = r#"
+            (defvar *depth* 0)
+            (defun depth () *depth*)
+            (defun bump () (setq *depth* (+ *depth* 100)))
+            (list (let ((*depth* 1)) (bump) (depth))
+                  (let* ((*depth* 2) (copy *depth*)) copy)
+                  (let ((*depth* 3) (old *depth*)) old)
+                  (depth))
+        "#;
+        assert_eq!(run_code(code), int_list(&[101, 2, 0, 0]));
+
+        let code = r#"
+            (defvar *x* 1)
+            (defun read-x () *x*)
+            (defun with-x (*x*) (read-x))
+            (list (with-x 2) (dotimes (*x* 3 (read-x))) (read-x))
+        "#;
+        assert_eq!(run_code(code), int_list(&[2, 3, 1]));
+
+        let code = r#"
+            (defvar *x* 1)
+            (dotimes (i 1) (let ((*x* 2)) (return)))
+            (+ *x* 0)
+        "#;
+        assert_eq!(run_code(code), LispValue::Int(1));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_depth(20);
+        let code = r#"
+            (defvar *x* 1)
+            (defun deep (n) (let ((*x* n)) (deep (+ n 1))))
+        "#;
+        interpreter.evaluate(code).unwrap();
+        assert!(interpreter.evaluate("(deep 0)").is_err());
+        assert_eq!(interpreter.evaluate("(+ *x* 0)"), Ok(LispValue::Int(1)));
+    }
+
+    #[test]
+    fn constants() {
+        let code = r#"
+            (defconstant pi-ish 3.14)
+            (defconstant pi-ish 3.14)
+            (defun area (r) (* pi-ish r r))
+            (area 2.0)
+        "#;
+        assert_eq!(run_code(code), LispValue::Float(12.56));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.evaluate("(defconstant limit 10)").unwrap();
+        let constant = Err(LispError::Constant("limit".to_string()));
+        assert_eq!(interpreter.evaluate("(setq limit 11)"), constant);
+        assert_eq!(interpreter.evaluate("(let ((limit 11)) limit)"), constant);
+        assert_eq!(interpreter.evaluate("(defvar limit 11)"), constant);
+        assert_eq!(interpreter.evaluate("(defparameter limit 11)"), constant);
+        assert_eq!(interpreter.evaluate("(defconstant limit 11)"), constant);
+        assert_eq!(
+            interpreter.evaluate("(funcall (lambda (limit) limit) 11)"),
+            constant
+        );
+        assert_eq!(interpreter.evaluate("(+ limit 0)"), Ok(LispValue::Int(10)));
+
+        assert_eq!(
+            interpreter.evaluate("(setq t nil)"),
+            Err(LispError::Constant("t".to_string()))
+        );
+        assert_eq!(
+            interpreter.evaluate("(let ((:key 1)) 2)"),
+            Err(LispError::Constant(":key".to_string()))
+        );
+    }
+
+    #[test]
+    fn documentation() {
+        let code = r#"
+            (defun square (n) "Multiplies a number by itself." (* n n))
+            (defun greeting () "Hello")
+            (defmacro twice (form) "Runs a form two times." (list 'progn form form))
+            (defvar *size* 10 "Size of the board.")
+            (defparameter *speed* 2 "Moves per turn.")
+            (defconstant limit 3 "Largest allowed value.")
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.evaluate(code).unwrap();
+
+        let string = |s: &str| Ok(LispValue::String(s.to_string()));
+        let mut documentation = |name: &str, doc_type: &str| {
+            interpreter.evaluate(&format!("(documentation '{} '{})", name, doc_type))
+        };
+        assert_eq!(
+            documentation("square", "function"),
+            string("Multiplies a number by itself.")
+        );
+        assert_eq!(
+            documentation("twice", "function"),
+            string("Runs a form two times.")
+        );
+        assert_eq!(
+            documentation("*size*", "variable"),
+            string("Size of the board.")
+        );
+        assert_eq!(
+            documentation("*speed*", "variable"),
+            string("Moves per turn.")
+        );
+        assert_eq!(
+            documentation("limit", "variable"),
+            string("Largest allowed value.")
+        );
+        assert_eq!(
+            documentation("car", "function"),
+            string("Returns the first element of a list.")
+        );
+        assert_eq!(documentation("greeting", "function"), Ok(LispValue::nil()));
+        assert_eq!(documentation("square", "variable"), Ok(LispValue::nil()));
+        assert_eq!(interpreter.evaluate("(square 3)"), Ok(LispValue::Int(9)));
+        assert_eq!(interpreter.evaluate("(greeting)"), string("Hello"));
+
+        assert_eq!(
+            documentation::describe_symbol(&interpreter.state, "square"),
+            "square names a function:\n  Documentation: Multiplies a number by itself."
+        );
+        assert_eq!(
+            documentation::describe_symbol(&interpreter.state, "*size*"),
+            "*size* names a special variable:\n  Value: 10\n  Documentation: Size of the board."
+        );
+        assert_eq!(
+            documentation::describe_symbol(&interpreter.state, "if"),
+            "if names a special operator:\n  Documentation: Evaluates the then form when the test is true, and the else form otherwise."
+        );
+        assert!(documentation::describe_symbol(&interpreter.state, "return")
+            .starts_with("return names a special operator:"));
+        assert_eq!(
+            documentation::describe_symbol(&interpreter.state, "unknown"),
+            "unknown is a symbol"
+        );
+    }
+
+    #[test]
+    fn bignums() {
+        let code = r#"
+            (defun factorial (n acc) (if (< n 2) acc (factorial (- n 1) (* n acc))))
+            (factorial 100 1)
+        "#;
+        let factorial = "93326215443944152681699238856266700490715968264381621468592963895217599993229915608941463976156518286253697920827223758251185210916864000000000000000000000000";
+        assert_eq!(
+            run_code(code),
+            LispValue::BigInt(BigInt::parse(factorial).unwrap())
+        );
+        assert_eq!(run_code(code).to_string(), factorial);
+
+        let big = |s: &str| LispValue::BigInt(BigInt::parse(s).unwrap());
+        assert_eq!(
+            run_code("(+ 9223372036854775807 1)"),
+            big("9223372036854775808")
+        );
+        assert_eq!(
+            run_code("(- -9223372036854775808 1)"),
+            big("-9223372036854775809")
+        );
+        assert_eq!(
+            run_code("(- 9223372036854775808 1)"),
+            LispValue::Int(i64::MAX)
+        );
+        assert_eq!(
+            run_code("(/ (* 4611686018427387904 4) 8)"),
+            LispValue::Int(2305843009213693952)
+        );
+        assert_eq!(
+            run_code("(+ 100000000000000000000 0.5)"),
+            LispValue::Float(1e20)
+        );
+
+        assert_eq!(
+            run_code("(< 9223372036854775807 9223372036854775808)"),
+            LispValue::t()
+        );
+        assert_eq!(run_code("(>= -100000000000000000000 1)"), LispValue::nil());
+        assert_eq!(
+            run_code("(= 100000000000000000000 (* 10000000000 10000000000))"),
+            LispValue::t()
+        );
+    }
+
+    #[test]
+    fn division() {
+        let ratio = |s: &str| LispValue::Ratio(Ratio::parse(s, 10).unwrap());
+        assert_eq!(run_code("(/ 8 2)"), LispValue::Int(4));
+        assert_eq!(run_code("(/ 7 2)"), ratio("7/2"));
+        assert_eq!(run_code("(/ 60 -2 3)"), LispValue::Int(-10));
+        assert_eq!(run_code("(/ 4)"), ratio("1/4"));
+        assert_eq!(run_code("(/ -1)"), LispValue::Int(-1));
+        assert_eq!(run_code("(/ 0.5)"), LispValue::Float(2.0));
+        assert_eq!(
+            run_code("(/ -9223372036854775808 -1)"),
+            run_code("(+ 9223372036854775807 1)")
+        );
+        assert_eq!(
+            run_code("(/ -9223372036854775808 -2)"),
+            LispValue::Int(4611686018427387904)
+        );
+        assert_eq!(run_code("(mod -9223372036854775808 -1)"), LispValue::Int(0));
+        assert_eq!(
+            run_code("(/ (* 3 100000000000000000000) 3)"),
+            LispValue::BigInt(BigInt::parse("100000000000000000000").unwrap())
+        );
+        assert_eq!(run_code("(div 6 3)"), LispValue::Int(2));
+        assert_eq!(run_code("(mul 6 3)"), LispValue::Int(18));
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(/ 1 0)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(/ 100000000000000000000 0)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(/ 1.5 0.0)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(/ 0)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(floor 1 0)"),
+            Err(LispError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn rounding() {
+        let values = |quotient: LispValue, remainder: LispValue| {
+            LispValue::Values(vec![quotient, remainder])
+        };
+        let int = LispValue::Int;
+        assert_eq!(run_code("(floor 7 2)"), values(int(3), int(1)));
+        assert_eq!(run_code("(floor -7 2)"), values(int(-4), int(1)));
+        assert_eq!(run_code("(floor 7 -2)"), values(int(-4), int(-1)));
+        assert_eq!(run_code("(truncate -7 2)"), values(int(-3), int(-1)));
+        assert_eq!(run_code("(round 5 2)"), values(int(2), int(1)));
+        assert_eq!(run_code("(round 7 2)"), values(int(4), int(-1)));
+        assert_eq!(run_code("(round -5 2)"), values(int(-2), int(-1)));
+        assert_eq!(run_code("(round 8 3)"), values(int(3), int(-1)));
+        assert_eq!(run_code("(floor 5)"), values(int(5), int(0)));
+        assert_eq!(
+            run_code("(floor 3.5)"),
+            values(int(3), LispValue::Float(0.5))
+        );
+        assert_eq!(
+            run_code("(round 2.5)"),
+            values(int(2), LispValue::Float(0.5))
+        );
+        assert_eq!(
+            run_code("(truncate -7.5 2)"),
+            values(int(-3), LispValue::Float(-1.5))
+        );
+        assert_eq!(run_code("(+ (floor 7 2) 1)"), int(4));
+
+        let big = |s: &str| LispValue::BigInt(BigInt::parse(s).unwrap());
+        assert_eq!(
+            run_code("(floor 100000000000000000000 3)"),
+            values(big("33333333333333333333"), int(1))
+        );
+        assert_eq!(
+            run_code("(floor 1.0e20)"),
+            values(big("100000000000000000000"), LispValue::Float(0.0))
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(floor 7 \"2\")"),
+            Err(LispError::ArgumentType {
+                function: "floor".to_string(),
+                position: 2,
+                expected: "real".to_string(),
+                value: LispValue::String("2".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn ratios() {
+        let ratio = |s: &str| LispValue::Ratio(Ratio::parse(s, 10).unwrap());
+        assert_eq!(run_code("(+ 3/4 0)"), ratio("3/4"));
+        assert_eq!(run_code("(+ 6/8 0)"), ratio("3/4"));
+        assert_eq!(run_code("(+ -4/2 0)"), LispValue::Int(-2));
+        assert_eq!(run_code("(+ 1/3 1/6)"), ratio("1/2"));
+        assert_eq!(run_code("(+ 1/2 1/2)"), LispValue::Int(1));
+        assert_eq!(run_code("(- 1 1/3)"), ratio("2/3"));
+        assert_eq!(run_code("(* 2/3 3/4 4)"), LispValue::Int(2));
+        assert_eq!(run_code("(/ 1/2 3)"), ratio("1/6"));
+        assert_eq!(run_code("(/ (/ 1 3) 1/3)"), LispValue::Int(1));
+        assert_eq!(run_code("(+ 1/4 0.5)"), LispValue::Float(0.75));
+        assert_eq!(
+            run_code("(* 1/100000000000000000000 100000000000000000000)"),
+            LispValue::Int(1)
+        );
+        assert_eq!(run_code("(+ 1/3 0)").to_string(), "1/3");
+
+        assert_eq!(run_code("(< 1/3 1/2 1)"), LispValue::t());
+        assert_eq!(run_code("(> 1/3 1/2)"), LispValue::nil());
+        assert_eq!(run_code("(= 1/2 2/4)"), LispValue::t());
+        assert_eq!(run_code("(= 1/2 0.5)"), LispValue::t());
+        assert_eq!(run_code("(<= 1/2 2/4)"), LispValue::t());
+        assert_eq!(run_code("(/= 1/2 1/3)"), LispValue::t());
+
+        let values = |quotient: LispValue, remainder: LispValue| {
+            LispValue::Values(vec![quotient, remainder])
+        };
+        assert_eq!(
+            run_code("(floor 7/2)"),
+            values(LispValue::Int(3), ratio("1/2"))
+        );
+        assert_eq!(
+            run_code("(round -7/2)"),
+            values(LispValue::Int(-4), ratio("1/2"))
+        );
+        assert_eq!(
+            run_code("(truncate 5 1/2)"),
+            values(LispValue::Int(10), LispValue::Int(0))
+        );
+
+        assert_eq!(run_code("(numerator 6/4)"), LispValue::Int(3));
+        assert_eq!(run_code("(denominator -6/4)"), LispValue::Int(2));
+        assert_eq!(run_code("(denominator 5)"), LispValue::Int(1));
+        assert_eq!(run_code("(rational 0.75)"), ratio("3/4"));
+        assert_eq!(
+            run_code("(rational 0.1)"),
+            ratio("3602879701896397/36028797018963968")
+        );
+        assert_eq!(run_code("(rationalize 0.1)"), ratio("1/10"));
+        assert_eq!(run_code("(rationalize 2.0)"), LispValue::Int(2));
+        assert_eq!(
+            run_code("(= (rationalize 1.0e308) (expt 10 308))"),
+            LispValue::t()
+        );
+        assert_eq!(run_code("(rationalize 2/3)"), ratio("2/3"));
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(numerator 0.5)"),
+            Err(LispError::TypeError {
+                function: "numerator".to_string(),
+                expected: "rational".to_string(),
+                value: LispValue::Float(0.5),
+            })
+        );
+    }
+
+    #[test]
+    fn numeric_contagion() {
+        let ratio = |s: &str| LispValue::Ratio(Ratio::parse(s, 10).unwrap());
+        assert_eq!(run_code("(+ 1 1/2)"), ratio("3/2"));
+        assert_eq!(run_code("(+ 1 1/2 0.5)"), LispValue::Float(2.0));
+        assert_eq!(
+            run_code("(* 100000000000000000000 1/400)"),
+            LispValue::Int(250000000000000000)
+        );
+        assert_eq!(run_code("(- 5)"), LispValue::Int(-5));
+        assert_eq!(run_code("(- 1/2)"), ratio("-1/2"));
+        assert_eq!(run_code("(*)"), LispValue::Int(1));
+        assert_eq!(run_code("(+)"), LispValue::Int(0));
+
+        assert_eq!(run_code("(<= 1 1 2)"), LispValue::t());
+        assert_eq!(run_code("(<= 1 2 1)"), LispValue::nil());
+        assert_eq!(run_code("(>= 3 3 2.5 1/2)"), LispValue::t());
+        assert_eq!(
+            run_code("(< 1 3/2 2.0 100000000000000000000)"),
+            LispValue::t()
+        );
+        assert_eq!(run_code("(= 1 1.0 2/2)"), LispValue::t());
+        assert_eq!(run_code("(/= 1 2 1.0)"), LispValue::nil());
+        assert_eq!(run_code("(< 5)"), LispValue::t());
+        assert_eq!(run_code("(= 1/3 0.3333333333333333)"), LispValue::nil());
+        assert_eq!(
+            run_code("(= 100000000000000000001 1.0e20)"),
+            LispValue::nil()
+        );
+        assert_eq!(run_code("(= 100000000000000000000 1.0e20)"), LispValue::t());
+        assert_eq!(
+            run_code("(> 9007199254740993 9007199254740992.0)"),
+            LispValue::t()
+        );
+        assert_eq!(
+            run_code("(/= 9007199254740993 9007199254740992.0)"),
+            LispValue::t()
+        );
+        assert_eq!(
+            run_code("(max 9007199254740993 9007199254740992.0)"),
+            run_code("(+ 9007199254740992 1)")
+        );
+
+        let mut interpreter = Interpreter::new();
+        let type_error = |function: &str, position, value| {
+            Err(LispError::ArgumentType {
+                function: function.to_string(),
+                position,
+                expected: "number".to_string(),
+                value,
+            })
+        };
+        let string = |s: &str| LispValue::String(s.to_string());
+        assert_eq!(
+            interpreter.evaluate("(+ 1 \"a\")"),
+            type_error("+", 2, string("a"))
+        );
+        assert_eq!(
+            interpreter.evaluate("(* 2 3 (quote x))"),
+            type_error("*", 3, LispValue::Name("x".to_string()))
+        );
+        assert_eq!(
+            interpreter.evaluate("(- nil 1)"),
+            type_error("-", 1, LispValue::nil())
+        );
+        assert_eq!(
+            interpreter.evaluate("(/ 1 \"0\")"),
+            type_error("/", 2, string("0"))
+        );
+        assert_eq!(
+            interpreter.evaluate("(- \"a\")"),
+            type_error("-", 1, string("a"))
+        );
+        assert_eq!(
+            interpreter.evaluate("(/ \"a\")"),
+            type_error("/", 1, string("a"))
+        );
+        assert_eq!(
+            interpreter.evaluate("(< 1 2 \"3\")"),
+            Err(LispError::ArgumentType {
+                function: "<".to_string(),
+                position: 3,
+                expected: "real".to_string(),
+                value: string("3"),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(= \"a\" \"a\")"),
+            type_error("=", 1, string("a"))
+        );
+        assert_eq!(
+            interpreter.evaluate("(+ 1 \"a\")").unwrap_err().to_string(),
+            "+: argument 2 is a, which is not of type number"
+        );
+    }
+
+    #[test]
+    fn math_library() {
+        let int = LispValue::Int;
+        let float = LispValue::Float;
+        let ratio = |s: &str| LispValue::Ratio(Ratio::parse(s, 10).unwrap());
+        let big = |s: &str| LispValue::BigInt(BigInt::parse(s).unwrap());
+        let values = |quotient: LispValue, remainder: LispValue| {
+            LispValue::Values(vec![quotient, remainder])
+        };
+
+        assert_eq!(run_code("(ceiling 7 2)"), values(int(4), int(-1)));
+        assert_eq!(run_code("(ceiling -7 2)"), values(int(-3), int(-1)));
+        assert_eq!(run_code("(ceiling 2.5)"), values(int(3), float(-0.5)));
+        assert_eq!(run_code("(mod -7 2)"), int(1));
+        assert_eq!(run_code("(rem -7 2)"), int(-1));
+        assert_eq!(run_code("(mod 7 -2)"), int(-1));
+        assert_eq!(run_code("(mod 5.5 2)"), float(1.5));
+        assert_eq!(
+            run_code("(1+ 9223372036854775807)"),
+            big("9223372036854775808")
+        );
+        assert_eq!(run_code("(1- 1/2)"), ratio("-1/2"));
+
+        assert_eq!(run_code("(abs -5)"), int(5));
+        assert_eq!(
+            run_code("(abs -9223372036854775808)"),
+            big("9223372036854775808")
+        );
+        assert_eq!(run_code("(abs -1/2)"), ratio("1/2"));
+        assert_eq!(run_code("(abs -2.5)"), float(2.5));
+        assert_eq!(run_code("(max 1 3 2)"), int(3));
+        assert_eq!(run_code("(min 1 1/2 0.75)"), ratio("1/2"));
+        assert_eq!(run_code("(max 2.0 5/2)"), ratio("5/2"));
+
+        assert_eq!(
+            run_code("(expt 2 100)"),
+            big("1267650600228229401496703205376")
+        );
+        assert_eq!(run_code("(expt 2 -2)"), ratio("1/4"));
+        assert_eq!(run_code("(expt 2/3 3)"), ratio("8/27"));
+        assert_eq!(run_code("(expt 5 0)"), int(1));
+        assert_eq!(run_code("(expt 2.0 3)"), float(8.0));
+        assert_eq!(run_code("(expt 4 1/2)"), float(2.0));
+        assert_eq!(run_code("(sqrt 16)"), float(4.0));
+        assert_eq!(run_code("(isqrt 17)"), int(4));
+        assert_eq!(run_code("(isqrt 100000000000000000000)"), int(10000000000));
+        assert_eq!(run_code("(exp 0)"), float(1.0));
+        assert_eq!(run_code("(log 1)"), float(0.0));
+        assert_eq!(run_code("(log 8 2)"), float(3.0));
+        assert_eq!(run_code("(sin 0)"), float(0.0));
+        assert_eq!(run_code("(cos 0)"), float(1.0));
+        assert_eq!(run_code("(atan 1 1)"), float(std::f64::consts::FRAC_PI_4));
+        assert_eq!(run_code("(tanh 0)"), float(0.0));
+        assert_eq!(run_code("(acosh 1)"), float(0.0));
+
+        assert_eq!(run_code("(gcd 12 -18 30)"), int(6));
+        assert_eq!(run_code("(gcd)"), int(0));
+        assert_eq!(run_code("(lcm 4 6)"), int(12));
+        assert_eq!(run_code("(lcm -4 6 0)"), int(0));
+        assert_eq!(run_code("(lcm)"), int(1));
+        assert_eq!(run_code("(signum -7)"), int(-1));
+        assert_eq!(run_code("(signum 1/3)"), int(1));
+        assert_eq!(run_code("(signum 0.0)"), float(0.0));
+        assert_eq!(run_code("(signum -2.5)"), float(-1.0));
+
+        assert_eq!(run_code("(zerop 0.0)"), LispValue::t());
+        assert_eq!(run_code("(plusp 1/2)"), LispValue::t());
+        assert_eq!(run_code("(minusp 0)"), LispValue::nil());
+        assert_eq!(run_code("(evenp 100000000000000000000)"), LispValue::t());
+        assert_eq!(run_code("(oddp -3)"), LispValue::t());
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(log 0)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(expt 0 -1)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(expt 0.0 -1)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(expt 0.0f0 -1/2)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(expt 0 -0.5)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(mod 1 0)"),
+            Err(LispError::DivisionByZero)
+        );
+        assert_eq!(
+            interpreter.evaluate("(gcd 4 1/2)"),
+            Err(LispError::ArgumentType {
+                function: "gcd".to_string(),
+                position: 2,
+                expected: "integer".to_string(),
+                value: ratio("1/2"),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(sin \"0\")"),
+            Err(LispError::ArgumentType {
+                function: "sin".to_string(),
+                position: 1,
+                expected: "real".to_string(),
+                value: LispValue::String("0".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn complex_numbers() {
+        let int = LispValue::Int;
+        let float = LispValue::Float;
+        let ratio = |s: &str| LispValue::Ratio(Ratio::parse(s, 10).unwrap());
+        let complex =
+            |real: LispValue, imag: LispValue| LispValue::Complex(Box::new(Complex { real, imag }));
+
+        assert_eq!(run_code("(+ #C(1 2) 0)"), complex(int(1), int(2)));
+        assert_eq!(run_code("(+ #c(1/2 -1) 0)"), complex(ratio("1/2"), int(-1)));
+        assert_eq!(run_code("(+ #C(1 0) 0)"), int(1));
+        assert_eq!(run_code("(+ #C(1.0 0) 0)"), complex(float(1.0), float(0.0)));
+        assert_eq!(
+            run_code("(list '#C(1 2))"),
+            LispValue::Function(vec![complex(int(1), int(2))])
+        );
+        assert_eq!(run_code("(complex 1 2)"), complex(int(1), int(2)));
+        assert_eq!(run_code("(complex 3)"), int(3));
+
+        assert_eq!(run_code("(* #C(0 1) #C(0 1))"), int(-1));
+        assert_eq!(run_code("(+ #C(1 2) #C(3 -2))"), int(4));
+        assert_eq!(run_code("(+ #C(1 2) 1.0)"), complex(float(2.0), float(2.0)));
+        assert_eq!(run_code("(- #C(1 2))"), complex(int(-1), int(-2)));
+        assert_eq!(run_code("(/ #C(1 1) #C(0 1))"), complex(int(1), int(-1)));
+        assert_eq!(run_code("(expt #C(0 1) 3)"), complex(int(0), int(-1)));
+        assert_eq!(run_code("(= #C(1 2) #C(1 2))"), LispValue::t());
+        assert_eq!(run_code("(/= #C(1 2) #C(1 -2))"), LispValue::t());
+        assert_eq!(run_code("(zerop #C(0.0 0.0))"), LispValue::t());
+
+        assert_eq!(run_code("(sqrt -4)"), complex(float(0.0), float(2.0)));
+        assert_eq!(run_code("(sqrt #C(3 4))"), complex(float(2.0), float(1.0)));
+        assert_eq!(
+            run_code("(log -1)"),
+            complex(float(0.0), float(std::f64::consts::PI))
+        );
+        assert_eq!(
+            run_code("(exp #C(0.0 0.0))"),
+            complex(float(1.0), float(0.0))
+        );
+        let single = LispValue::SingleFloat;
+        assert_eq!(run_code("(sqrt -4.0f0)"), complex(single(0.0), single(2.0)));
+        assert_eq!(
+            run_code("(sqrt #C(3f0 4f0))"),
+            complex(single(2.0), single(1.0))
+        );
+        assert_eq!(
+            run_code("(log -1f0)"),
+            complex(single(0.0), single(std::f32::consts::PI))
+        );
+        assert_eq!(
+            run_code("(exp #C(0f0 0f0))"),
+            complex(single(1.0), single(0.0))
+        );
+        assert_eq!(
+            run_code("(expt 0f0 #C(1f0 1f0))"),
+            complex(single(0.0), single(0.0))
+        );
+
+        assert_eq!(run_code("(abs #C(3 4))"), float(5.0));
+        assert_eq!(
+            run_code("(phase #C(0 1))"),
+            float(std::f64::consts::FRAC_PI_2)
+        );
+        assert_eq!(run_code("(phase -1)"), float(std::f64::consts::PI));
+        assert_eq!(run_code("(conjugate #C(1 2))"), complex(int(1), int(-2)));
+        assert_eq!(run_code("(conjugate 5)"), int(5));
+        assert_eq!(run_code("(realpart #C(1/2 3))"), ratio("1/2"));
+        assert_eq!(run_code("(imagpart #C(1/2 3))"), int(3));
+        assert_eq!(run_code("(imagpart 2.5)"), float(0.0));
+        assert_eq!(
+            to_print_string(&complex(int(1), ratio("-1/2"))),
+            "#C(1 -1/2)"
+        );
+
+        let mut interpreter = Interpreter::new();
+        let illegal = Err(LispError::Parse(
+            "illegal complex number format".to_string(),
+        ));
+        assert_eq!(interpreter.evaluate("#C(1)"), illegal);
+        assert_eq!(interpreter.evaluate("(list #C(1 2 3))"), illegal);
+        assert_eq!(interpreter.evaluate("'#C(a b)"), illegal);
+        assert_eq!(
+            interpreter.evaluate("(< #C(1 1) 2)"),
+            Err(LispError::ArgumentType {
+                function: "<".to_string(),
+                position: 1,
+                expected: "real".to_string(),
+                value: complex(int(1), int(1)),
+            })
+        );
+    }
+
+    #[test]
+    fn bitwise_operations() {
+        let int = LispValue::Int;
+        let big = |s: &str| LispValue::BigInt(BigInt::parse(s).unwrap());
+
+        assert_eq!(run_code("(logand 12 10)"), int(8));
+        assert_eq!(run_code("(logand)"), int(-1));
+        assert_eq!(run_code("(logior 12 10 1)"), int(15));
+        assert_eq!(run_code("(logxor 12 10)"), int(6));
+        assert_eq!(run_code("(logand -1 255)"), int(255));
+        assert_eq!(run_code("(logand -16 255)"), int(240));
+        assert_eq!(run_code("(lognot 0)"), int(-1));
+        assert_eq!(run_code("(lognot 9223372036854775807)"), int(i64::MIN));
+        assert_eq!(
+            run_code("(logand 36893488147419103231 -18446744073709551616)"),
+            big("18446744073709551616")
+        );
+        assert_eq!(
+            run_code("(logxor -1 18446744073709551616)"),
+            big("-18446744073709551617")
+        );
+        assert_eq!(run_code("(logtest 4 3)"), LispValue::nil());
+        assert_eq!(run_code("(logtest -1 1)"), LispValue::t());
+
+        assert_eq!(run_code("(ash 1 70)"), big("1180591620717411303424"));
+        assert_eq!(run_code("(ash 1180591620717411303424 -70)"), int(1));
+        assert_eq!(run_code("(ash -5 -1)"), int(-3));
+        assert_eq!(run_code("(ash -5 -100)"), int(-1));
+        assert_eq!(run_code("(logbitp 1 6)"), LispValue::t());
+        assert_eq!(run_code("(logbitp 200 -1)"), LispValue::t());
+        assert_eq!(
+            run_code("(logbitp 64 18446744073709551616)"),
+            LispValue::t()
+        );
+        assert_eq!(run_code("(logcount 13)"), int(3));
+        assert_eq!(run_code("(logcount -13)"), int(2));
+        assert_eq!(run_code("(integer-length 255)"), int(8));
+        assert_eq!(run_code("(integer-length -256)"), int(8));
+        assert_eq!(run_code("(integer-length 18446744073709551616)"), int(65));
+
+        assert_eq!(run_code("(ldb (byte 8 8) 43981)"), int(171));
+        assert_eq!(run_code("(ldb (byte 4 0) -1)"), int(15));
+        assert_eq!(
+            run_code("(ldb (byte 8 64) 5534023222112865484800)"),
+            int(44)
+        );
+        assert_eq!(run_code("(dpb 255 (byte 8 8) 0)"), int(65280));
+        assert_eq!(run_code("(dpb 0 (byte 4 0) -1)"), int(-16));
+        assert_eq!(
+            run_code("(dpb 1 (byte 1 64) 0)"),
+            big("18446744073709551616")
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(logand 1 1.0)"),
+            Err(LispError::ArgumentType {
+                function: "logand".to_string(),
+                position: 2,
+                expected: "integer".to_string(),
+                value: LispValue::Float(1.0),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(ldb 8 255)"),
+            Err(LispError::ArgumentType {
+                function: "ldb".to_string(),
+                position: 1,
+                expected: "byte specifier".to_string(),
+                value: int(8),
+            })
+        );
+    }
+
+    #[test]
+    fn random_numbers() {
+        let sequence =
+            "(list (random 100) (random 100000000000000000000) (random 1.5) (random 100))";
+        let mut first = Interpreter::new();
+        let mut second = Interpreter::new();
+        first.set_random_seed(42);
+        second.set_random_seed(42);
+        let numbers = first.evaluate(sequence).unwrap();
+        assert_eq!(second.evaluate(sequence), Ok(numbers.clone()));
+        second.set_random_seed(43);
+        assert_ne!(second.evaluate(sequence), Ok(numbers));
+
+        assert_eq!(run_code("(< -1 (random 10) 10)"), LispValue::t());
+        assert_eq!(run_code("(<= 0.0 (random 0.5) 0.5)"), LispValue::t());
+        assert_eq!(run_code("(random 1)"), LispValue::Int(0));
+        let code =
+            "(let ((copy (make-random-state nil))) (= (random 1000000) (random 1000000 copy)))";
+        assert_eq!(run_code(code), LispValue::t());
+        let code = "(let ((*random-state* (make-random-state 7))) (random 1000000))";
+        assert_eq!(run_code(code), run_code(code));
+        let code = "(let ((a (make-random-state 7)) (b (make-random-state 7))) (random 10 a) (= (random 1000000 a) (random 1000000 b)))";
+        assert_eq!(run_code(code), LispValue::nil());
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(random 0)"),
+            Err(LispError::ArgumentType {
+                function: "random".to_string(),
+                position: 1,
+                expected: "(or (integer 1 *) (float (0.0) *))".to_string(),
+                value: LispValue::Int(0),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(random 10 5)"),
+            Err(LispError::ArgumentType {
+                function: "random".to_string(),
+                position: 2,
+                expected: "random-state".to_string(),
+                value: LispValue::Int(5),
+            })
+        );
+    }
+
+    #[test]
+    fn number_conversion() {
+        let int = LispValue::Int;
+        let string = |s: &str| LispValue::String(s.to_string());
+        let values = |a: LispValue, b: LispValue| LispValue::Values(vec![a, b]);
+
+        assert_eq!(
+            run_code("(parse-integer \"  42 \")"),
+            values(int(42), int(5))
+        );
+        assert_eq!(
+            run_code("(parse-integer \"-ff\" :radix 16)"),
+            values(int(-255), int(3))
+        );
+        assert_eq!(
+            run_code("(parse-integer \"abc123def\" :start 3 :end 6)"),
+            values(int(123), int(6))
+        );
+        assert_eq!(
+            run_code("(parse-integer \"12x\" :junk-allowed t)"),
+            values(int(12), int(2))
+        );
+        assert_eq!(
+            run_code("(parse-integer \"x\" :junk-allowed t)"),
+            values(LispValue::nil(), int(0))
+        );
+        assert_eq!(
+            run_code("(parse-integer \"100000000000000000000\")"),
+            values(
+                LispValue::BigInt(BigInt::parse("100000000000000000000").unwrap()),
+                int(21)
+            )
+        );
+
+        assert_eq!(
+            run_code("(read-from-string \" 42 rest\")"),
+            values(int(42), int(4))
+        );
+        assert_eq!(
+            run_code("(read-from-string \"1/2\")"),
+            values(LispValue::Ratio(Ratio::parse("1/2", 10).unwrap()), int(3))
+        );
+        assert_eq!(
+            run_code("(read-from-string \"(1 2) x\")"),
+            values(LispValue::Function(vec![int(1), int(2)]), int(5))
+        );
+        assert_eq!(
+            run_code("(read-from-string \"  \" nil :eof)"),
+            values(LispValue::Name(":eof".to_string()), int(2))
+        );
+        assert_eq!(
+            run_code("(let ((*read-base* 16)) (read-from-string \"ff\"))"),
+            values(int(255), int(2))
+        );
+        assert_eq!(
+            run_code("(let ((*read-base* 2)) (read-from-string \"10.\"))"),
+            values(int(10), int(3))
+        );
+
+        assert_eq!(run_code("(write-to-string 255 :base 16)"), string("FF"));
+        assert_eq!(
+            run_code("(write-to-string 255 :base 16 :radix t)"),
+            string("#xFF")
+        );
+        assert_eq!(
+            run_code("(write-to-string -5 :base 2 :radix t)"),
+            string("#b-101")
+        );
+        assert_eq!(run_code("(write-to-string 42 :radix t)"), string("42."));
+        assert_eq!(
+            run_code("(write-to-string 1/3 :base 3 :radix t)"),
+            string("#3r1/10")
+        );
+        assert_eq!(
+            run_code("(write-to-string (list 8 \"a\") :base 8)"),
+            string("(10 \"a\")")
+        );
+        assert_eq!(
+            run_code("(princ-to-string (list 8 \"a\"))"),
+            string("(8 a)")
+        );
+        assert_eq!(
+            run_code("(let ((*print-base* 16)) (princ-to-string 3054))"),
+            string("BEE")
+        );
+        assert_eq!(
+            run_code("(let ((*print-base* 16) (*print-radix* t)) (princ-to-string 10))"),
+            string("#xA")
+        );
+
+        assert_eq!(run_code("(setq *read-base* 16) (+ ff 1)"), int(256));
+        assert_eq!(run_code("(setq *read-base* 16) 10"), int(16));
+        assert_eq!(
+            run_code("(setq *read-base* 16) (setq *read-base* a) 10"),
+            int(10)
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(parse-integer \"12x\")"),
+            Err(LispError::Parse("junk in string \"12x\"".to_string()))
+        );
+        assert_eq!(
+            interpreter.evaluate("(parse-integer \" \")"),
+            Err(LispError::Parse("no digits in string \" \"".to_string()))
+        );
+        assert_eq!(
+            interpreter.evaluate("(parse-integer \"1\" :radix 37)"),
+            Err(LispError::TypeError {
+                function: "parse-integer".to_string(),
+                expected: "(integer 2 36)".to_string(),
+                value: int(37),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(write-to-string 1 :bass 2)"),
+            Err(LispError::UnknownKeyword(LispValue::Name(
+                ":bass".to_string()
+            )))
+        );
+
+        let parse_error = |message: &str| Err(LispError::Parse(message.to_string()));
+        assert_eq!(
+            interpreter.evaluate("(read-from-string \")\")"),
+            parse_error("unmatched close parenthesis")
+        );
+        assert_eq!(
+            interpreter.evaluate("(read-from-string \";\")"),
+            parse_error("end of file in string")
+        );
+        assert_eq!(
+            interpreter.evaluate("(read-from-string \"'\")"),
+            parse_error("end of file")
+        );
+        assert_eq!(
+            interpreter.evaluate("(read-from-string \"(1 2\")"),
+            parse_error("end of file in list")
+        );
+        assert_eq!(
+            interpreter.evaluate("(read-from-string \"; note\" nil :eof)"),
+            Ok(LispValue::Values(vec![
+                LispValue::Name(":eof".to_string()),
+                int(6)
+            ]))
+        );
+        assert_eq!(
+            interpreter.evaluate("(read-from-string \"' (a) b\")"),
+            Ok(LispValue::Values(vec![
+                LispValue::Function(vec![
+                    LispValue::Name("quote".to_string()),
+                    LispValue::Function(vec![LispValue::Name("a".to_string())])
+                ]),
+                int(5)
+            ]))
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let int = LispValue::Int;
+        let string = |s: &str| LispValue::String(s.to_string());
+        let single = LispValue::SingleFloat;
+        let double = LispValue::Float;
+
+        assert_eq!(run_code("(princ-to-string 1.0)"), string("1.0"));
+        assert_eq!(run_code("(princ-to-string 1.0e20)"), string("1.0e20"));
+        assert_eq!(
+            run_code("(princ-to-string (list 0.1 -1.5e-7 123.456))"),
+            string("(0.1 -1.5e-7 123.456)")
+        );
+        assert_eq!(run_code("(princ-to-string 1.5f0)"), string("1.5f0"));
+        assert_eq!(
+            run_code("(princ-to-string #C(1.0 2.0))"),
+            string("#C(1.0 2.0)")
+        );
+        assert_eq!(
+            run_code("(let ((*read-default-float-format* 'single-float)) (princ-to-string (list 1.5f0 1.5d0)))"),
+            string("(1.5 1.5d0)")
+        );
+        assert_eq!(
+            run_code("(setq *read-default-float-format* 'single-float) (+ 2.5)"),
+            single(2.5)
+        );
+
+        assert_eq!(run_code("(+ 1.5f0 1)"), single(2.5));
+        assert_eq!(run_code("(+ 1.5f0 1.0)"), double(2.5));
+        assert_eq!(run_code("(* 2 0.25s0)"), single(0.5));
+        assert_eq!(run_code("(sqrt 4f0)"), single(2.0));
+        assert_eq!(run_code("(= 0.5f0 1/2)"), LispValue::t());
+
+        assert_eq!(run_code("(float 1)"), double(1.0));
+        assert_eq!(run_code("(float 1/4 1.0f0)"), single(0.25));
+        assert_eq!(run_code("(float 1.5f0)"), single(1.5));
+        assert_eq!(run_code("(coerce 1/2 'single-float)"), single(0.5));
+        assert_eq!(
+            run_code("(coerce 0.1f0 'double-float)"),
+            double(0.1f32 as f64)
+        );
+        assert_eq!(run_code("(coerce 3 'float)"), double(3.0));
+        assert_eq!(
+            run_code("(decode-float 8.0)"),
+            LispValue::Values(vec![double(0.5), int(4), double(1.0)])
+        );
+        assert_eq!(
+            run_code("(decode-float -0.75f0)"),
+            LispValue::Values(vec![single(0.75), int(0), single(-1.0)])
+        );
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.evaluate("(coerce 1 'string)"),
+            Err(LispError::TypeError {
+                function: "coerce".to_string(),
+                expected: "string".to_string(),
+                value: int(1),
+            })
+        );
+        assert_eq!(
+            interpreter.evaluate("(decode-float 1)"),
+            Err(LispError::ArgumentType {
+                function: "decode-float".to_string(),
+                position: 1,
+                expected: "float".to_string(),
+                value: int(1),
+            })
+        );
+    }
+}
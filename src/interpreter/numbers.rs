@@ -0,0 +1,652 @@
+use super::basic::*;
+use super::bignum::BigInt;
+use super::complex::*;
+use super::floats::*;
+use super::ratio::Ratio;
+use super::types::*;
+use crate::parser::LispValue;
+use std::cmp::Ordering;
+
+/// How `floor`, `truncate` and `round` pick the integer quotient.
+#[derive(Clone, Copy)]
+pub enum Rounding {
+    Floor,
+    Ceiling,
+    Truncate,
+    /// To the nearest integer, and to the even one on ties.
+    Round,
+}
+
+impl Rounding {
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            Rounding::Floor => value.floor(),
+            Rounding::Ceiling => value.ceil(),
+            Rounding::Truncate => value.trunc(),
+            Rounding::Round => value.round_ties_even(),
+        }
+    }
+}
+
+/// Levels of the numeric tower. Arithmetic and comparison convert all their
+/// arguments to the highest level among them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Integer,
+    Ratio,
+    Float,
+    Complex,
+}
+
+fn level(value: &LispValue) -> Option<Level> {
+    match value {
+        LispValue::Int(_) | LispValue::BigInt(_) => Some(Level::Integer),
+        LispValue::Ratio(_) => Some(Level::Ratio),
+        LispValue::Float(_) | LispValue::SingleFloat(_) => Some(Level::Float),
+        LispValue::Complex(_) => Some(Level::Complex),
+        _ => None,
+    }
+}
+
+/// Returns the level of the tower the arguments share, when none is above
+/// `highest`. Other arguments are a type error naming their position,
+/// counted from one.
+fn tower_level(
+    function: &str,
+    expected: &str,
+    highest: Level,
+    args: &[LispValue],
+) -> Result<Level, LispError> {
+    let mut result = Level::Integer;
+    for (position, arg) in args.iter().enumerate() {
+        match level(arg) {
+            Some(level) if level <= highest => result = result.max(level),
+            _ => {
+                return Err(LispError::ArgumentType {
+                    function: function.to_string(),
+                    position: position + 1,
+                    expected: expected.to_string(),
+                    value: arg.clone(),
+                })
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the level of the tower the numbers share.
+pub fn contagion(function: &str, args: &[LispValue]) -> Result<Level, LispError> {
+    tower_level(function, "number", Level::Complex, args)
+}
+
+/// Returns the level of the tower the reals share, for functions that do not
+/// take complex numbers.
+pub fn real_contagion(function: &str, args: &[LispValue]) -> Result<Level, LispError> {
+    tower_level(function, "real", Level::Float, args)
+}
+
+fn small_integers(args: &[LispValue]) -> Option<Vec<i64>> {
+    args.iter()
+        .map(|arg| match arg {
+            LispValue::Int(i) => Some(*i),
+            _ => None,
+        })
+        .collect()
+}
+
+fn integers(args: &[LispValue]) -> Vec<BigInt> {
+    args.iter().filter_map(to_bignum).collect()
+}
+
+fn rationals(args: &[LispValue]) -> Vec<Ratio> {
+    args.iter().filter_map(to_ratio).collect()
+}
+
+fn floats(args: &[LispValue]) -> Vec<f64> {
+    args.iter().map(to_float).collect()
+}
+
+pub fn add(args: Vec<LispValue>) -> LispResult {
+    let result = match contagion("+", &args)? {
+        Level::Integer => fold_integers(&args, i64::checked_add, |a, b| Some(a.add(b))),
+        Level::Ratio => fold_rationals(&args, |a, b| Some(a.add(b))),
+        Level::Float => Some(float_result(&args, perform_add(&floats(&args)))),
+        Level::Complex => return fold_complex(&args, complex_add),
+    };
+    Ok(result.unwrap_or_else(LispValue::nil))
+}
+
+/// Subtracts the other numbers from the first one, or negates a single
+/// number.
+pub fn sub(mut args: Vec<LispValue>) -> LispResult {
+    if args.len() == 1 {
+        // The argument is checked before the implicit zero shifts it.
+        contagion("-", &args)?;
+        args.insert(0, LispValue::Int(0));
+    }
+
+    let result = match contagion("-", &args)? {
+        Level::Integer => fold_integers(&args, i64::checked_sub, |a, b| Some(a.sub(b))),
+        Level::Ratio => fold_rationals(&args, |a, b| Some(a.sub(b))),
+        Level::Float => Some(float_result(&args, perform_sub(&floats(&args)))),
+        Level::Complex => return fold_complex(&args, complex_sub),
+    };
+    Ok(result.unwrap_or_else(LispValue::nil))
+}
+
+pub fn mul(mut args: Vec<LispValue>) -> LispResult {
+    if args.is_empty() {
+        args.push(LispValue::Int(1));
+    }
+
+    let result = match contagion("*", &args)? {
+        Level::Integer => fold_integers(&args, i64::checked_mul, |a, b| Some(a.mul(b))),
+        Level::Ratio => fold_rationals(&args, |a, b| Some(a.mul(b))),
+        Level::Float => Some(float_result(&args, perform_mul(&floats(&args)))),
+        Level::Complex => return fold_complex(&args, complex_mul),
+    };
+    Ok(result.unwrap_or_else(LispValue::nil))
+}
+
+/// Divides integers that divide evenly. Returns None otherwise, or when the
+/// quotient overflows, so the caller falls back to bignums or ratios.
+fn exact_div(a: i64, b: i64) -> Option<i64> {
+    if a.checked_rem(b)? != 0 {
+        return None;
+    }
+    a.checked_div(b)
+}
+
+fn exact_div_big(a: &BigInt, b: &BigInt) -> Option<BigInt> {
+    let (quotient, remainder) = a.div_rem(b)?;
+    remainder.is_zero().then_some(quotient)
+}
+
+/// Divides the first number by the others, or returns the reciprocal of a
+/// single number. Rationals divide exactly, with a fast path for integers
+/// that divide evenly.
+pub fn div(mut args: Vec<LispValue>) -> LispResult {
+    if args.is_empty() {
+        return Ok(LispValue::nil());
+    }
+    if args.len() == 1 {
+        contagion("/", &args)?;
+        args.insert(0, LispValue::Int(1));
+    }
+
+    let level = contagion("/", &args)?;
+    if args.iter().skip(1).any(is_zero) {
+        return Err(LispError::DivisionByZero);
+    }
+
+    let result = match level {
+        Level::Integer => fold_integers(&args, exact_div, exact_div_big)
+            .or_else(|| fold_rationals(&args, Ratio::div)),
+        Level::Ratio => fold_rationals(&args, Ratio::div),
+        Level::Float => Some(float_result(&args, perform_div(&floats(&args)))),
+        Level::Complex => return fold_complex(&args, complex_div),
+    };
+    Ok(result.unwrap_or_else(LispValue::nil))
+}
+
+/// Tells whether `test` holds for the ordering of every pair of neighbouring
+/// reals, as for `<`.
+pub fn compare(
+    function: &str,
+    args: Vec<LispValue>,
+    test: fn(Ordering) -> bool,
+) -> Result<Option<bool>, LispError> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+
+    let result = match real_contagion(function, &args)? {
+        Level::Integer => match small_integers(&args) {
+            Some(integers) => perform_compare(&integers, test),
+            None => perform_compare(&integers(&args), test),
+        },
+        Level::Ratio => perform_compare(&rationals(&args), test),
+        Level::Float | Level::Complex => args
+            .windows(2)
+            .all(|pair| compare_numbers(&pair[0], &pair[1]).is_some_and(test)),
+    };
+    Ok(Some(result))
+}
+
+/// Tells whether all the numbers are equal.
+pub fn equals(args: Vec<LispValue>) -> Result<Option<bool>, LispError> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+
+    match contagion("=", &args)? {
+        Level::Complex => Ok(Some(
+            args.windows(2)
+                .all(|pair| complex_equal(&pair[0], &pair[1])),
+        )),
+        _ => compare("=", args, Ordering::is_eq),
+    }
+}
+
+/// Tells whether no two of the numbers are equal.
+pub fn not_equals(args: Vec<LispValue>) -> Result<Option<bool>, LispError> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+
+    let result = match contagion("/=", &args)? {
+        Level::Integer => perform_not_equals(&integers(&args)),
+        Level::Ratio => perform_not_equals(&rationals(&args)),
+        Level::Float => args.iter().enumerate().all(|(i, a)| {
+            args[i + 1..]
+                .iter()
+                .all(|b| compare_numbers(a, b) != Some(Ordering::Equal))
+        }),
+        Level::Complex => args
+            .iter()
+            .enumerate()
+            .all(|(i, a)| args[i + 1..].iter().all(|b| !complex_equal(a, b))),
+    };
+    Ok(Some(result))
+}
+
+fn real(function: &str, value: LispValue) -> Result<LispValue, LispError> {
+    match level(&value) {
+        Some(level) if level < Level::Complex => Ok(value),
+        _ => Err(type_error(function, "real", value)),
+    }
+}
+
+fn rational(function: &str, value: LispValue) -> Result<Ratio, LispError> {
+    match to_ratio(&value) {
+        Some(ratio) => Ok(ratio),
+        None => Err(type_error(function, "rational", value)),
+    }
+}
+
+/// Divides integers, adjusting the truncated quotient by one when the
+/// rounding asks for it. `divisor` must not be zero.
+fn round_integers(rounding: Rounding, number: &BigInt, divisor: &BigInt) -> (BigInt, BigInt) {
+    let (quotient, remainder) = number.div_rem(divisor).unwrap_or_default();
+    if remainder.is_zero() {
+        return (quotient, remainder);
+    }
+
+    let positive = remainder.is_negative() == divisor.is_negative();
+    let step = match rounding {
+        Rounding::Truncate => false,
+        Rounding::Floor => !positive,
+        Rounding::Ceiling => positive,
+        Rounding::Round => match remainder.add(&remainder).abs().cmp(&divisor.abs()) {
+            Ordering::Less => false,
+            Ordering::Equal => quotient.is_odd(),
+            Ordering::Greater => true,
+        },
+    };
+    if !step {
+        return (quotient, remainder);
+    }
+
+    // Moves the quotient one step away from zero.
+    match positive {
+        true => (quotient.add(&BigInt::from(1)), remainder.sub(divisor)),
+        false => (quotient.sub(&BigInt::from(1)), remainder.add(divisor)),
+    }
+}
+
+/// Divides a number by a divisor, one by default, and returns the rounded
+/// quotient and the remainder as two values.
+pub fn divide_rounding(function: &str, rounding: Rounding, mut args: Vec<LispValue>) -> LispResult {
+    let divisor = match args.len() {
+        1 => LispValue::Int(1),
+        2 => args.remove(1),
+        _ => return Ok(LispValue::nil()),
+    };
+    let number = args.remove(0);
+    let format = result_format(&[number.clone(), divisor.clone()]);
+    real_contagion(function, &[number.clone(), divisor.clone()])?;
+    if is_zero(&divisor) {
+        return Err(LispError::DivisionByZero);
+    }
+
+    if let (Some(number), Some(divisor)) = (to_ratio(&number), to_ratio(&divisor)) {
+        let exact = number.div(&divisor).ok_or(LispError::DivisionByZero)?;
+        let (quotient, _) = round_integers(rounding, exact.numerator(), exact.denominator());
+        let remainder = number.sub(&divisor.mul(&Ratio::from_integer(quotient.clone())));
+        return Ok(LispValue::Values(vec![
+            LispValue::integer(quotient),
+            LispValue::rational(remainder),
+        ]));
+    }
+
+    let (number, divisor) = (to_float(&number), to_float(&divisor));
+    let quotient = rounding.apply(number / divisor);
+    match BigInt::from_f64(quotient) {
+        Some(integral) => Ok(LispValue::Values(vec![
+            LispValue::integer(integral),
+            format.float(number - quotient * divisor),
+        ])),
+        None => Err(type_error(
+            function,
+            "finite float",
+            LispValue::Float(quotient),
+        )),
+    }
+}
+
+pub fn numerator(mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let ratio = rational("numerator", args.remove(0))?;
+    Ok(LispValue::integer(ratio.numerator().clone()))
+}
+
+pub fn denominator(mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let ratio = rational("denominator", args.remove(0))?;
+    Ok(LispValue::integer(ratio.denominator().clone()))
+}
+
+/// Converts a real to a rational. `rational` gives the exact value of a
+/// float, and `rationalize` the simplest fraction that reads back as the same
+/// float.
+pub fn to_rational(
+    function: &str,
+    convert: fn(f64) -> Option<Ratio>,
+    mut args: Vec<LispValue>,
+) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    match real(function, args.remove(0))? {
+        value @ (LispValue::Float(_) | LispValue::SingleFloat(_)) => {
+            match convert(to_float(&value)) {
+                Some(ratio) => Ok(LispValue::rational(ratio)),
+                None => Err(type_error(function, "finite float", value)),
+            }
+        }
+        value => Ok(value),
+    }
+}
+
+/// Returns the remainder of `floor` for `mod`, or of `truncate` for `rem`.
+pub fn remainder(function: &str, rounding: Rounding, args: Vec<LispValue>) -> LispResult {
+    if args.len() != 2 {
+        return Ok(LispValue::nil());
+    }
+
+    match divide_rounding(function, rounding, args)? {
+        LispValue::Values(mut values) => Ok(values.pop().unwrap_or_else(LispValue::nil)),
+        value => Ok(value),
+    }
+}
+
+/// Adds `step` to a number, for `1+` and `1-`.
+pub fn increment(function: &str, step: i64, args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    contagion(function, &args)?;
+    add(vec![args[0].clone(), LispValue::Int(step)])
+}
+
+pub fn abs(args: Vec<LispValue>) -> LispResult {
+    let value = match args.as_slice() {
+        [value] => value,
+        _ => return Ok(LispValue::nil()),
+    };
+
+    if contagion("abs", &args)? == Level::Complex {
+        let (real, imag) = parts(value);
+        return Ok(float_result(&[real, imag], magnitude(value)));
+    }
+    let result = map_number(value, i64::checked_abs, BigInt::abs, Ratio::abs, f64::abs);
+    Ok(result.unwrap_or_else(LispValue::nil))
+}
+
+/// Returns the greatest of the numbers for `max`, or the least for `min`.
+pub fn extreme(function: &str, keep: Ordering, args: Vec<LispValue>) -> LispResult {
+    real_contagion(function, &args)?;
+    Ok(perform_extreme(&args, keep).unwrap_or_else(LispValue::nil))
+}
+
+/// Raises a number to a power. Rationals and rational complex numbers raised
+/// to integers are exact, negative reals raised to fractions are complex, and
+/// other powers are floats.
+pub fn expt(args: Vec<LispValue>) -> LispResult {
+    let (base, power) = match args.as_slice() {
+        [base, power] => (base, power),
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let level = contagion("expt", &args)?;
+    if is_zero(base) && compare_numbers(power, &LispValue::Int(0)) == Some(Ordering::Less) {
+        return Err(LispError::DivisionByZero);
+    }
+    if let (Some(base), LispValue::Int(power)) = (to_ratio(base), power) {
+        let one = Ratio::from_integer(BigInt::from(1));
+        let result = perform_power(&base, power.unsigned_abs(), one.clone(), Ratio::mul);
+        return match *power < 0 {
+            true => one
+                .div(&result)
+                .map(LispValue::rational)
+                .ok_or(LispError::DivisionByZero),
+            false => Ok(LispValue::rational(result)),
+        };
+    }
+
+    if let (LispValue::Complex(_), LispValue::Int(exponent)) = (base, power) {
+        let multiply =
+            |a: &LispValue, b: &LispValue| complex_mul(a, b).unwrap_or_else(|_| LispValue::nil());
+        let result = perform_power(base, exponent.unsigned_abs(), LispValue::Int(1), multiply);
+        return match *exponent < 0 {
+            true => complex_div(&LispValue::Int(1), &result),
+            false => Ok(result),
+        };
+    }
+
+    let negative = compare_numbers(base, &LispValue::Int(0)) == Some(Ordering::Less);
+    let integral = matches!(power, LispValue::Float(_) | LispValue::SingleFloat(_) if to_float(power).fract() == 0.0);
+    if level == Level::Complex || negative && !integral {
+        return complex_expt(base, power);
+    }
+    Ok(float_result(&args, to_float(base).powf(to_float(power))))
+}
+
+/// The square root of a number, which is complex for negative reals.
+pub fn sqrt(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let negative = compare_numbers(&args[0], &LispValue::Int(0)) == Some(Ordering::Less);
+    match contagion("sqrt", &args)? {
+        Level::Complex => Ok(complex_sqrt(&args[0])),
+        _ if negative => Ok(complex_sqrt(&args[0])),
+        _ => Ok(float_result(&args, to_float(&args[0]).sqrt())),
+    }
+}
+
+pub fn exp(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    match contagion("exp", &args)? {
+        Level::Complex => Ok(complex_exp(&args[0])),
+        _ => Ok(float_result(&args, to_float(&args[0]).exp())),
+    }
+}
+
+/// Applies a float function to a number, for `sqrt`, `exp`, `sin` and the
+/// like.
+pub fn float_function(
+    function: &str,
+    operation: fn(f64) -> f64,
+    args: Vec<LispValue>,
+) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    real_contagion(function, &args)?;
+    Ok(float_result(&args, operation(to_float(&args[0]))))
+}
+
+/// The arc tangent of a number, or of `y/x` in the quadrant of the point.
+pub fn atan(args: Vec<LispValue>) -> LispResult {
+    match args.as_slice() {
+        [_] => float_function("atan", f64::atan, args),
+        [y, x] => {
+            real_contagion("atan", &args)?;
+            Ok(float_result(&args, to_float(y).atan2(to_float(x))))
+        }
+        _ => Ok(LispValue::nil()),
+    }
+}
+
+fn natural_log(number: &LispValue) -> Result<LispValue, LispError> {
+    if is_zero(number) {
+        return Err(LispError::DivisionByZero);
+    }
+
+    match compare_numbers(number, &LispValue::Int(0)) {
+        Some(Ordering::Greater) => Ok(float_result(
+            std::slice::from_ref(number),
+            to_float(number).ln(),
+        )),
+        _ => Ok(complex_log(number)),
+    }
+}
+
+/// The natural logarithm of a number, or its logarithm in the given base.
+/// Negative reals have complex logarithms.
+pub fn log(args: Vec<LispValue>) -> LispResult {
+    let (number, base) = match args.as_slice() {
+        [number] => (number, None),
+        [number, base] => (number, Some(base)),
+        _ => return Ok(LispValue::nil()),
+    };
+
+    contagion("log", &args)?;
+    let result = natural_log(number)?;
+    match base {
+        Some(base) => div(vec![result, natural_log(base)?]),
+        None => Ok(result),
+    }
+}
+
+/// Returns the integers among the arguments, or a type error naming the
+/// first argument that is not one.
+pub fn integer_arguments(function: &str, args: &[LispValue]) -> Result<Vec<BigInt>, LispError> {
+    let mut integers = Vec::new();
+    for (position, arg) in args.iter().enumerate() {
+        match to_bignum(arg) {
+            Some(integer) => integers.push(integer),
+            None => {
+                return Err(LispError::ArgumentType {
+                    function: function.to_string(),
+                    position: position + 1,
+                    expected: "integer".to_string(),
+                    value: arg.clone(),
+                })
+            }
+        }
+    }
+    Ok(integers)
+}
+
+/// The greatest integer whose square is not above a non-negative integer.
+pub fn isqrt(args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let integer = integer_arguments("isqrt", &args)?.remove(0);
+    match integer.isqrt() {
+        Some(root) => Ok(LispValue::integer(root)),
+        None => Err(type_error("isqrt", "non-negative integer", args[0].clone())),
+    }
+}
+
+pub fn gcd(args: Vec<LispValue>) -> LispResult {
+    let integers = integer_arguments("gcd", &args)?;
+    let result = integers.iter().fold(BigInt::default(), |a, b| a.gcd(b));
+    Ok(LispValue::integer(result))
+}
+
+pub fn lcm(args: Vec<LispValue>) -> LispResult {
+    let integers = integer_arguments("lcm", &args)?;
+    let result = integers
+        .iter()
+        .fold(BigInt::from(1), |a, b| match a.div_rem(&a.gcd(b)) {
+            Some((quotient, _)) => quotient.mul(b).abs(),
+            None => BigInt::default(),
+        });
+    Ok(LispValue::integer(result))
+}
+
+/// Returns -1, 0 or 1 for the sign of a number, as a float for floats.
+pub fn signum(args: Vec<LispValue>) -> LispResult {
+    let value = match args.as_slice() {
+        [value] => value,
+        _ => return Ok(LispValue::nil()),
+    };
+
+    contagion("signum", &args)?;
+    match value {
+        LispValue::Complex(_) => div(vec![value.clone(), LispValue::Float(magnitude(value))]),
+        LispValue::Float(f) if *f == 0.0 || f.is_nan() => Ok(LispValue::Float(*f)),
+        LispValue::Float(f) => Ok(LispValue::Float(f.signum())),
+        LispValue::SingleFloat(f) if *f == 0.0 || f.is_nan() => Ok(LispValue::SingleFloat(*f)),
+        LispValue::SingleFloat(f) => Ok(LispValue::SingleFloat(f.signum())),
+        value => {
+            let sign = compare_numbers(value, &LispValue::Int(0)).map_or(0, |sign| sign as i64);
+            Ok(LispValue::Int(sign))
+        }
+    }
+}
+
+pub fn zerop(args: Vec<LispValue>) -> Result<Option<bool>, LispError> {
+    if args.len() != 1 {
+        return Ok(None);
+    }
+
+    contagion("zerop", &args)?;
+    let (real, imag) = parts(&args[0]);
+    Ok(Some(is_zero(&real) && is_zero(&imag)))
+}
+
+/// Tells whether the ordering of a real to zero passes `test`, for `plusp`
+/// and `minusp`.
+pub fn sign_test(
+    function: &str,
+    test: fn(Ordering) -> bool,
+    args: Vec<LispValue>,
+) -> Result<Option<bool>, LispError> {
+    if args.len() != 1 {
+        return Ok(None);
+    }
+
+    real_contagion(function, &args)?;
+    Ok(Some(
+        compare_numbers(&args[0], &LispValue::Int(0)).is_some_and(test),
+    ))
+}
+
+/// Tells whether an integer is odd for `oddp`, or even for `evenp`.
+pub fn parity(function: &str, odd: bool, args: Vec<LispValue>) -> Result<Option<bool>, LispError> {
+    if args.len() != 1 {
+        return Ok(None);
+    }
+
+    let integer = integer_arguments(function, &args)?.remove(0);
+    Ok(Some(integer.is_odd() == odd))
+}
@@ -0,0 +1,213 @@
+use super::basic::to_float;
+use super::bignum::BigInt;
+use super::floats::FloatFormat;
+use super::types::*;
+use crate::parser::LispValue;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const RANDOM_STATE: &str = "*random-state*";
+
+/// Distinguishes states seeded from the clock within the same tick.
+static FRESH_STATES: AtomicU64 = AtomicU64::new(0);
+
+/// The state of a xoshiro256** generator. The same seed always gives the
+/// same sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RandomState {
+    s: [u64; 4],
+}
+
+impl RandomState {
+    /// Expands a seed into the four words of the state with splitmix64, which
+    /// never makes them all zero.
+    pub fn from_seed(seed: u64) -> Self {
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Self {
+            s: [next(), next(), next(), next()],
+        }
+    }
+
+    /// A state seeded from the clock, different for every call.
+    pub fn fresh() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        let count = FRESH_STATES.fetch_add(1, Ordering::Relaxed);
+        Self::from_seed(nanos ^ count.rotate_left(32))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    /// A uniform integer below a positive limit. Values from the incomplete
+    /// last cycle of the limit are drawn again, so there is no bias.
+    pub fn below(&mut self, limit: u64) -> u64 {
+        let threshold = limit.wrapping_neg() % limit;
+        loop {
+            let value = self.next_u64();
+            if value >= threshold {
+                return value % limit;
+            }
+        }
+    }
+
+    /// A uniform integer below a positive limit of any size, drawing as many
+    /// bits as the limit has until the result is below it.
+    pub fn below_bignum(&mut self, limit: &BigInt) -> BigInt {
+        let bits = limit.bit_length();
+        let words = bits.div_ceil(32);
+        loop {
+            let mut value = BigInt::default();
+            for _ in 0..words {
+                let word = BigInt::from((self.next_u64() >> 32) as i64);
+                value = value.shl(32).add(&word);
+            }
+            let value = value.shr(words * 32 - bits);
+            if value < *limit {
+                return value;
+            }
+        }
+    }
+
+    /// A uniform float in [0, 1) with 53 random bits.
+    pub fn float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+pub fn random_state_value(state: RandomState) -> LispValue {
+    LispValue::RandomState(Rc::new(RefCell::new(state)))
+}
+
+fn current_state(state: &State) -> Result<Rc<RefCell<RandomState>>, LispError> {
+    match state.get_value(RANDOM_STATE) {
+        Some(LispValue::RandomState(random_state)) => Ok(random_state),
+        value => Err(type_error(
+            "random",
+            "random-state",
+            value.unwrap_or_else(LispValue::nil),
+        )),
+    }
+}
+
+/// Returns a random number of the type of the limit, from zero up to but not
+/// including it. The state is `*random-state*` unless one is given.
+pub fn random(state: &State, args: Vec<LispValue>) -> LispResult {
+    let random_state = match args.as_slice() {
+        [_] => current_state(state)?,
+        [_, LispValue::RandomState(random_state)] => random_state.clone(),
+        [_, value] => {
+            return Err(LispError::ArgumentType {
+                function: "random".to_string(),
+                position: 2,
+                expected: "random-state".to_string(),
+                value: value.clone(),
+            })
+        }
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let mut random_state = random_state.borrow_mut();
+    match &args[0] {
+        LispValue::Int(limit) if *limit > 0 => {
+            Ok(LispValue::Int(random_state.below(*limit as u64) as i64))
+        }
+        LispValue::BigInt(limit) if !limit.is_negative() => {
+            Ok(LispValue::integer(random_state.below_bignum(limit)))
+        }
+        limit @ (LispValue::Float(_) | LispValue::SingleFloat(_))
+            if to_float(limit) > 0.0 && to_float(limit).is_finite() =>
+        {
+            let format = FloatFormat::of(limit).unwrap_or(FloatFormat::Double);
+            loop {
+                // Rounding to a single float can reach the limit, so those
+                // values are drawn again.
+                let value = format.float(random_state.float() * to_float(limit));
+                if to_float(&value) < to_float(limit) {
+                    return Ok(value);
+                }
+            }
+        }
+        limit => Err(LispError::ArgumentType {
+            function: "random".to_string(),
+            position: 1,
+            expected: "(or (integer 1 *) (float (0.0) *))".to_string(),
+            value: limit.clone(),
+        }),
+    }
+}
+
+/// Makes a random state: a copy of `*random-state*` for nil, a copy of the
+/// given state, a state seeded from the clock for t, or a state seeded with
+/// a non-negative integer.
+pub fn make_random_state(state: &State, args: Vec<LispValue>) -> LispResult {
+    let argument = match args.as_slice() {
+        [] => LispValue::nil(),
+        [argument] => argument.clone(),
+        _ => return Ok(LispValue::nil()),
+    };
+
+    let result = match &argument {
+        value if value.is_nil() => current_state(state)?.borrow().clone(),
+        LispValue::RandomState(random_state) => random_state.borrow().clone(),
+        LispValue::Int(seed) if *seed >= 0 => RandomState::from_seed(*seed as u64),
+        value if *value == LispValue::t() => RandomState::fresh(),
+        value => {
+            return Err(type_error(
+                "make-random-state",
+                "(or random-state boolean (integer 0 *))",
+                value.clone(),
+            ))
+        }
+    };
+    Ok(random_state_value(result))
+}
+
+#[cfg(test)]
+mod random_test {
+    use super::*;
+
+    #[test]
+    fn seeded_sequences() {
+        let mut a = RandomState::from_seed(42);
+        let mut b = RandomState::from_seed(42);
+        let first: Vec<u64> = (0..10).map(|_| a.below(1000)).collect();
+        let second: Vec<u64> = (0..10).map(|_| b.below(1000)).collect();
+        assert_eq!(first, second);
+        assert_ne!(RandomState::from_seed(1), RandomState::from_seed(2));
+        assert_ne!(RandomState::fresh(), RandomState::fresh());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut state = RandomState::from_seed(7);
+        let limit = BigInt::parse("100000000000000000000").unwrap();
+        for _ in 0..1000 {
+            assert!(state.below(3) < 3);
+            assert!((0.0..1.0).contains(&state.float()));
+            let value = state.below_bignum(&limit);
+            assert!(!value.is_negative() && value < limit);
+        }
+        assert_eq!(state.below(1), 0);
+    }
+}
@@ -0,0 +1,328 @@
+use super::bignum::BigInt;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// An exact fraction in lowest terms. The denominator is always positive, so
+/// equal fractions have equal representations.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Ratio {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+impl Ratio {
+    /// Reduces the fraction. Returns None when the denominator is zero.
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Option<Self> {
+        if denominator.is_zero() {
+            return None;
+        }
+
+        let divisor = numerator.gcd(&denominator);
+        let (numerator, _) = numerator.div_rem(&divisor)?;
+        let (denominator, _) = denominator.div_rem(&divisor)?;
+        match denominator.is_negative() {
+            true => Some(Self {
+                numerator: numerator.neg(),
+                denominator: denominator.neg(),
+            }),
+            false => Some(Self {
+                numerator,
+                denominator,
+            }),
+        }
+    }
+
+    pub fn from_integer(value: BigInt) -> Self {
+        Self {
+            numerator: value,
+            denominator: BigInt::from(1),
+        }
+    }
+
+    /// The exact value of a float. Returns None for infinities and NaN.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        let bits = value.to_bits();
+        let (mantissa, exponent) = match ((bits >> 52) & 0x7ff) as i32 {
+            0 => (bits & ((1 << 52) - 1), -1074),
+            exponent => ((bits & ((1 << 52) - 1)) | (1 << 52), exponent - 1075),
+        };
+        let mantissa = match value < 0.0 {
+            true => BigInt::from(-(mantissa as i64)),
+            false => BigInt::from(mantissa as i64),
+        };
+
+        match exponent >= 0 {
+            true => Some(Self::from_integer(mantissa.shl(exponent as u32))),
+            false => Self::new(mantissa, BigInt::from(1).shl(-exponent as u32)),
+        }
+    }
+
+    /// The simplest fraction that converts back to the same float: the one
+    /// with the smallest denominator strictly between the halfway points to
+    /// the neighbouring floats. When integers lie between them, that is the
+    /// one with the fewest significant digits, so `1.0e308` gives 10^308.
+    pub fn rationalize(value: f64) -> Option<Self> {
+        let magnitude = value.abs();
+        if magnitude == 0.0 {
+            return Self::from_f64(0.0);
+        }
+
+        let exact = Self::from_f64(magnitude)?;
+        let below = Self::from_f64(f64::from_bits(magnitude.to_bits() - 1))?;
+        let two = Self::from_integer(BigInt::from(2));
+        let low = exact.add(&below).div(&two)?;
+        let high = match Self::from_f64(f64::from_bits(magnitude.to_bits() + 1)) {
+            Some(above) => exact.add(&above).div(&two)?,
+            None => exact.add(&exact.sub(&below).div(&two)?),
+        };
+
+        let result = match Self::roundest_integer(&low, &high) {
+            Some(integer) => Self::from_integer(integer),
+            None => Self::simplest_between(low, high)?,
+        };
+        match value < 0.0 {
+            true => Some(result.neg()),
+            false => Some(result),
+        }
+    }
+
+    /// The integer with the most trailing zeros in the open interval from
+    /// `low` to `high`, with `low` not negative, or None when the interval
+    /// holds no integer. Of several, it is the least.
+    fn roundest_integer(low: &Self, high: &Self) -> Option<BigInt> {
+        let ten = BigInt::from(10);
+        let mut scale = BigInt::from(1);
+        let mut roundest = None;
+        loop {
+            let (floor, _) = low.numerator.div_rem(&low.denominator.mul(&scale))?;
+            let candidate = floor.add(&BigInt::from(1)).mul(&scale);
+            if Self::from_integer(candidate.clone()) >= *high {
+                return roundest;
+            }
+            roundest = Some(candidate);
+            scale = scale.mul(&ten);
+        }
+    }
+
+    /// The fraction with the smallest denominator in the open interval from
+    /// `low` to `high`, with `low` not negative. Builds its continued fraction
+    /// term by term: while no integer lies in the interval, the integer part
+    /// is common to every fraction in it and the rest of the search continues
+    /// on the reciprocals of the fractional parts.
+    fn simplest_between(mut low: Self, mut high: Self) -> Option<Self> {
+        let one = Self::from_integer(BigInt::from(1));
+        let (mut h0, mut h1) = (BigInt::from(0), BigInt::from(1));
+        let (mut k0, mut k1) = (BigInt::from(1), BigInt::from(0));
+        let mut bounded = true;
+
+        loop {
+            let (floor, _) = low.numerator.div_rem(&low.denominator)?;
+            let next = floor.add(&BigInt::from(1));
+            let term = match !bounded || Self::from_integer(next.clone()) < high {
+                true => next,
+                false => floor.clone(),
+            };
+            let h2 = term.mul(&h1).add(&h0);
+            let k2 = term.mul(&k1).add(&k0);
+            if term != floor {
+                return Self::new(h2, k2);
+            }
+
+            let integer = Self::from_integer(floor);
+            let fraction = low.sub(&integer);
+            low = one.div(&high.sub(&integer))?;
+            match fraction.numerator.is_zero() {
+                true => bounded = false,
+                false => high = one.div(&fraction)?,
+            }
+            (h0, h1) = (h1, h2);
+            (k0, k1) = (k1, k2);
+        }
+    }
+
+    /// Reads `numerator/denominator` with digits in a radix from 2 to 36 and
+    /// an optional sign on the numerator.
+    pub fn parse(text: &str, radix: u32) -> Option<Self> {
+        let (numerator, denominator) = text.split_once('/')?;
+        if !denominator.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        Self::new(
+            BigInt::parse_radix(numerator, radix)?,
+            BigInt::parse_radix(denominator, radix)?,
+        )
+    }
+
+    /// Writes the fraction in a radix from 2 to 36.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        match self.is_integer() {
+            true => self.numerator.to_string_radix(radix),
+            false => format!(
+                "{}/{}",
+                self.numerator.to_string_radix(radix),
+                self.denominator.to_string_radix(radix)
+            ),
+        }
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == BigInt::from(1)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator.to_f64() / self.denominator.to_f64()
+    }
+
+    pub fn neg(&self) -> Self {
+        Self {
+            numerator: self.numerator.neg(),
+            denominator: self.denominator.clone(),
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            numerator: self.numerator.abs(),
+            denominator: self.denominator.clone(),
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let numerator = self
+            .numerator
+            .mul(&other.denominator)
+            .add(&other.numerator.mul(&self.denominator));
+        let denominator = self.denominator.mul(&other.denominator);
+        Self::new(numerator, denominator).unwrap_or_else(|| self.clone())
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let numerator = self.numerator.mul(&other.numerator);
+        let denominator = self.denominator.mul(&other.denominator);
+        Self::new(numerator, denominator).unwrap_or_else(|| self.clone())
+    }
+
+    /// Returns None when dividing by zero.
+    pub fn div(&self, other: &Self) -> Option<Self> {
+        let numerator = self.numerator.mul(&other.denominator);
+        let denominator = self.denominator.mul(&other.numerator);
+        Self::new(numerator, denominator)
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let left = self.numerator.mul(&other.denominator);
+        let right = other.numerator.mul(&self.denominator);
+        left.cmp(&right)
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.is_integer() {
+            true => write!(f, "{}", self.numerator),
+            false => write!(f, "{}/{}", self.numerator, self.denominator),
+        }
+    }
+}
+
+#[cfg(test)]
+mod ratio_test {
+    use super::*;
+
+    fn ratio(text: &str) -> Ratio {
+        Ratio::parse(text, 10).unwrap()
+    }
+
+    #[test]
+    fn normalization() {
+        assert_eq!(ratio("6/4").to_string(), "3/2");
+        assert_eq!(ratio("-6/4").to_string(), "-3/2");
+        assert_eq!(ratio("8/4").to_string(), "2");
+        assert_eq!(ratio("0/7").to_string(), "0");
+        assert_eq!(
+            Ratio::new(BigInt::from(3), BigInt::from(-9)),
+            Some(ratio("-1/3"))
+        );
+        assert_eq!(Ratio::parse("1/0", 10), None);
+        assert_eq!(Ratio::parse("1/-2", 10), None);
+        assert_eq!(Ratio::parse("/", 10), None);
+        assert_eq!(Ratio::parse("-a/14", 16), Some(ratio("-1/2")));
+        assert_eq!(ratio("-10/3").to_string_radix(2), "-1010/11");
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(ratio("1/3").add(&ratio("1/6")), ratio("1/2"));
+        assert_eq!(ratio("1/3").sub(&ratio("1/2")), ratio("-1/6"));
+        assert_eq!(ratio("2/3").mul(&ratio("9/4")), ratio("3/2"));
+        assert_eq!(ratio("2/3").div(&ratio("-4/9")), Some(ratio("-3/2")));
+        assert_eq!(ratio("2/3").div(&ratio("0/1")), None);
+        assert!(ratio("1/3") < ratio("1/2"));
+        assert!(ratio("-1/2") < ratio("-1/3"));
+        assert_eq!(ratio("3/4").to_f64(), 0.75);
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(Ratio::from_f64(0.75), Some(ratio("3/4")));
+        assert_eq!(Ratio::from_f64(-2.0), Some(ratio("-2/1")));
+        assert_eq!(
+            Ratio::from_f64(0.1),
+            Some(ratio("3602879701896397/36028797018963968"))
+        );
+        assert_eq!(Ratio::rationalize(0.1), Some(ratio("1/10")));
+        assert_eq!(Ratio::rationalize(-0.75), Some(ratio("-3/4")));
+        assert_eq!(Ratio::rationalize(3.0), Some(ratio("3/1")));
+        // 333333333/1000000000 reads back as the same float too, but it is not
+        // the fraction with the smallest denominator that does.
+        assert_eq!(
+            Ratio::rationalize(0.333333333),
+            Some(ratio("333333315/999999946"))
+        );
+        assert_eq!(Ratio::rationalize(1.0 / 3.0), Some(ratio("1/3")));
+        assert_eq!(Ratio::rationalize(-2.71), Some(ratio("-271/100")));
+        assert_eq!(Ratio::rationalize(1e-5), Some(ratio("1/100000")));
+        assert_eq!(
+            Ratio::rationalize(0.1 + 0.2),
+            Some(ratio("415716888680356/1385722962267853"))
+        );
+        assert_eq!(Ratio::rationalize(0.0), Some(ratio("0/1")));
+        let power = |exponent: usize| ratio(&format!("1{}/1", "0".repeat(exponent)));
+        assert_eq!(Ratio::rationalize(1.0e308), Some(power(308)));
+        assert_eq!(Ratio::rationalize(-1.0e20), Some(power(20).neg()));
+        assert_eq!(
+            Ratio::rationalize(1.5e300),
+            Some(ratio(&format!("15{}/1", "0".repeat(299))))
+        );
+        assert_eq!(Ratio::rationalize(123456789.0), Some(ratio("123456789/1")));
+        assert_eq!(
+            Ratio::rationalize(f64::MAX),
+            Some(ratio(&format!("17976931348623157{}/1", "0".repeat(292))))
+        );
+        assert_eq!(Ratio::from_f64(f64::NAN), None);
+    }
+}
@@ -1,27 +1,252 @@
 use crate::parser::LispValue;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
 
+use super::bignum::BigInt;
+use super::floats::{format_float, FloatFormat};
 use super::functions::*;
+use super::lambda_list::LambdaList;
+use super::macros::*;
+use super::values::*;
 
-pub enum Type {
-    None,
-    Int,
-    Float,
-    Boolean,
-    String,
+/// Number of nested function calls allowed before a stack exhausted error.
+pub const DEFAULT_MAX_DEPTH: usize = 10000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LispError {
+    StackExhausted(usize),
+    UnboundVariable(String),
+    /// A call of a name with no function, or of a macro or special form
+    /// through `funcall`, the `undefined-function` condition.
+    UndefinedFunction(String),
+    /// A form whose operator is neither a name nor a lambda expression.
+    IllegalFunctionCall(LispValue),
+    /// Assignment, binding or redefinition of a constant.
+    Constant(String),
+    ArgumentCount {
+        expected: String,
+        given: usize,
+    },
+    UnknownKeyword(LispValue),
+    /// A number divided by zero, the `division-by-zero` condition.
+    DivisionByZero,
+    /// Text that cannot be read, the `parse-error` condition.
+    Parse(String),
+    /// Non-local exit of `return` to the loop with the given block number,
+    /// the innermost loop around it in the code.
+    Return(usize, LispValue),
+    /// A `return` with no loop around it in the code.
+    ReturnOutsideLoop,
+    TypeError {
+        function: String,
+        expected: String,
+        value: LispValue,
+    },
+    /// A type error in one of several arguments, numbered from one.
+    ArgumentType {
+        function: String,
+        position: usize,
+        expected: String,
+        value: LispValue,
+    },
+}
+
+impl fmt::Display for LispError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LispError::StackExhausted(depth) => {
+                write!(f, "Control stack exhausted (depth {})", depth)
+            }
+            LispError::UnboundVariable(name) => write!(f, "The variable {} is unbound", name),
+            LispError::UndefinedFunction(name) => write!(f, "The function {} is undefined", name),
+            LispError::IllegalFunctionCall(form) => {
+                write!(f, "Illegal function call: {}", to_print_string(form))
+            }
+            LispError::Constant(name) => write!(f, "{} is a constant and cannot be changed", name),
+            LispError::ArgumentCount { expected, given } => {
+                write!(
+                    f,
+                    "Invalid number of arguments: {}, expected {}",
+                    given, expected
+                )
+            }
+            LispError::UnknownKeyword(keyword) => {
+                write!(f, "Unknown keyword argument {}", to_print_string(keyword))
+            }
+            LispError::DivisionByZero => write!(f, "Arithmetic error: division by zero"),
+            LispError::Parse(message) => write!(f, "Parse error: {}", message),
+            LispError::Return(..) => write!(f, "Return from a loop that has already ended"),
+            LispError::ReturnOutsideLoop => write!(f, "Return outside of a loop"),
+            LispError::TypeError {
+                function,
+                expected,
+                value,
+            } => write!(
+                f,
+                "{}: the value {} is not of type {}",
+                function,
+                to_print_string(value),
+                expected
+            ),
+            LispError::ArgumentType {
+                function,
+                position,
+                expected,
+                value,
+            } => write!(
+                f,
+                "{}: argument {} is {}, which is not of type {}",
+                function,
+                position,
+                to_print_string(value),
+                expected
+            ),
+        }
+    }
 }
+
+pub type LispResult = Result<LispValue, LispError>;
+
+/// A type error of `function` for a value that is not of type `expected`.
+pub fn type_error(function: &str, expected: &str, value: LispValue) -> LispError {
+    LispError::TypeError {
+        function: function.to_string(),
+        expected: expected.to_string(),
+        value,
+    }
+}
+
+/// Checks that a builtin got from `min` to `max` arguments, or at least
+/// `min` when there is no `max`.
+pub fn check_argument_count(
+    args: &[LispValue],
+    min: usize,
+    max: Option<usize>,
+) -> Result<(), LispError> {
+    let given = args.len();
+    if given >= min && max.is_none_or(|max| given <= max) {
+        return Ok(());
+    }
+
+    let expected = match max {
+        None => format!("at least {}", min),
+        Some(max) if max == min => min.to_string(),
+        Some(max) => format!("{} to {}", min, max),
+    };
+    Err(LispError::ArgumentCount { expected, given })
+}
+
 #[warn(dead_code)]
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Function {
     Add,
     Sub,
     Mul,
     Div,
-    //    Print,
+    Floor,
+    Truncate,
+    Round,
+    Ceiling,
+    Mod,
+    Rem,
+    OnePlus,
+    OneMinus,
+    Abs,
+    Min,
+    Max,
+    Expt,
+    Sqrt,
+    Isqrt,
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Gcd,
+    Lcm,
+    Signum,
+    Zerop,
+    Plusp,
+    Minusp,
+    Evenp,
+    Oddp,
+    Numerator,
+    Denominator,
+    Rational,
+    Rationalize,
+    Complex,
+    Realpart,
+    Imagpart,
+    Conjugate,
+    Phase,
+    Logand,
+    Logior,
+    Logxor,
+    Lognot,
+    Logtest,
+    Ash,
+    Logbitp,
+    Logcount,
+    IntegerLength,
+    Byte,
+    Ldb,
+    Dpb,
+    Random,
+    MakeRandomState,
+    ParseInteger,
+    ReadFromString,
+    WriteToString,
+    PrincToString,
+    Float,
+    Coerce,
+    DecodeFloat,
     Println,
     Defvar,
+    Defparameter,
+    Defconstant,
     Defun,
-    // Setq,
+    Setq,
+    Let,
+    LetStar,
+    If,
+    Progn,
+    Dotimes,
+    Dolist,
+    Do,
+    DoStar,
+    Loop,
+    While,
+    Return,
+    Cons,
+    Car,
+    Cdr,
+    First,
+    Rest,
+    List,
+    ListStar,
+    Append,
+    Length,
+    Nth,
+    Nthcdr,
+    Last,
+    Butlast,
+    Reverse,
+    Nreverse,
+    Member,
+    CopyList,
+    Null,
+    Endp,
     Equals,
     NotEquals,
     Grater,
@@ -31,160 +256,493 @@ pub enum Function {
     Or,
     And,
     Not,
-    Custom(Vec<String>, Vec<LispValue>),
+    Lambda,
+    SharpQuote,
+    Funcall,
+    Apply,
+    Defmacro,
+    Macroexpand1,
+    Macroexpand,
+    Gensym,
+    Quote,
+    Eval,
+    Values,
+    MultipleValueBind,
+    MultipleValueList,
+    MultipleValueCall,
+    NthValue,
+    Documentation,
+    Describe,
+    Custom(Box<LambdaList>, Vec<LispValue>, State),
+    /// A function whose arguments are passed unevaluated and whose result is
+    /// evaluated in place of the call.
+    Macro(Box<LambdaList>, Vec<LispValue>, State),
+}
+
+/// The kinds of docstrings a name can have.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DocType {
+    Function,
+    Variable,
+}
+
+/// Result of evaluating a form in tail position. Calls are returned to the
+/// caller instead of being executed, so it can run them without growing the
+/// stack.
+pub enum Tail {
+    Value(LispValue),
+    Call(Function, Vec<LispValue>),
 }
 
-pub struct State<'a> {
-    parent: Option<&'a State<'a>>,
+/// A single scope with its own functions and variables. Frames are shared
+/// between the states that reference them, so closures can keep their
+/// defining frame alive and inner scopes can assign to outer variables.
+pub struct Frame {
+    parent: Option<Rc<RefCell<Frame>>>,
     functions: HashMap<String, Function>,
-    pub variables: HashMap<String, LispValue>,
+    variables: HashMap<String, LispValue>,
+    /// Global values of the special variables bound in this frame, restored
+    /// when the scope of the frame ends.
+    saved: Vec<(String, Option<LispValue>)>,
+    /// The number of the loop whose body this frame is, for `return`.
+    block: Option<usize>,
+}
+
+/// Interpreter wide data shared by every state created from the same root.
+struct Context {
+    depth: Cell<usize>,
+    max_depth: Cell<usize>,
+    boolean_compatibility: Cell<bool>,
+    gensym_counter: Cell<usize>,
+    block_counter: Cell<usize>,
+    specials: RefCell<HashSet<String>>,
+    constants: RefCell<HashMap<String, LispValue>>,
+    documentation: RefCell<HashMap<(String, DocType), String>>,
+}
+
+#[derive(Clone)]
+pub struct State {
+    frame: Rc<RefCell<Frame>>,
+    context: Rc<Context>,
 }
 
-impl<'a> State<'a> {
+impl State {
     pub fn new() -> Self {
         let parent = None;
         let functions = HashMap::new();
         let variables = HashMap::new();
-        Self {
+        let frame = Frame {
             parent,
             functions,
             variables,
+            saved: Vec::new(),
+            block: None,
+        };
+        let context = Context {
+            depth: Cell::new(0),
+            max_depth: Cell::new(DEFAULT_MAX_DEPTH),
+            boolean_compatibility: Cell::new(false),
+            gensym_counter: Cell::new(1),
+            block_counter: Cell::new(0),
+            specials: RefCell::new(HashSet::new()),
+            constants: RefCell::new(HashMap::from([("t".to_string(), LispValue::t())])),
+            documentation: RefCell::new(HashMap::new()),
+        };
+        Self {
+            frame: Rc::new(RefCell::new(frame)),
+            context: Rc::new(context),
         }
     }
 
-    pub fn create_child(&'a self) -> State<'a> {
-        let parent = Some(self);
+    pub fn create_child(&self) -> State {
+        let parent = Some(self.frame.clone());
         let functions = HashMap::new();
         let variables = HashMap::new();
-        Self {
+        let frame = Frame {
             parent,
             functions,
             variables,
+            saved: Vec::new(),
+            block: None,
+        };
+        Self {
+            frame: Rc::new(RefCell::new(frame)),
+            context: self.context.clone(),
         }
     }
 
-    pub fn get_function(&self, name: &str) -> Option<&Function> {
-        let mut function = self.functions.get(name);
-        if let None = function {
-            if let Some(parent_state) = &self.parent {
-                function = parent_state.get_function(name);
-            }
+    pub fn max_depth(&self) -> usize {
+        self.context.max_depth.get()
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.context.max_depth.set(max_depth);
+    }
+
+    /// Enables the `true` and `false` booleans of older lisper code. Predicates
+    /// then return them instead of `t` and `nil`.
+    pub fn set_boolean_compatibility(&mut self, enabled: bool) {
+        self.context.boolean_compatibility.set(enabled);
+    }
+
+    pub fn boolean_compatibility(&self) -> bool {
+        self.context.boolean_compatibility.get()
+    }
+
+    /// Converts the result of a predicate to a lisp value.
+    pub fn boolean(&self, value: bool) -> LispValue {
+        match (self.boolean_compatibility(), value) {
+            (true, value) => LispValue::Boolean(value),
+            (false, true) => LispValue::t(),
+            (false, false) => LispValue::nil(),
         }
-        function
     }
 
-    pub fn get_value(&self, name: &str) -> Option<&LispValue> {
-        let mut value = self.variables.get(name);
-        if let None = value {
-            if let Some(parent_state) = &self.parent {
-                value = parent_state.get_value(name);
-            }
+    /// Counts a new nested function call, failing once the depth limit is
+    /// reached. Every successful call must be matched by `leave_call`.
+    pub fn enter_call(&mut self) -> Result<(), LispError> {
+        let depth = self.context.depth.get();
+        if depth >= self.context.max_depth.get() {
+            return Err(LispError::StackExhausted(depth));
+        }
+        self.context.depth.set(depth + 1);
+        Ok(())
+    }
+
+    pub fn leave_call(&mut self) {
+        self.context.depth.set(self.context.depth.get() - 1);
+    }
+
+    /// Returns the number for the next `gensym` symbol.
+    pub fn next_gensym(&self) -> usize {
+        let counter = self.context.gensym_counter.get();
+        self.context.gensym_counter.set(counter + 1);
+        counter
+    }
+
+    /// Returns a child state that is the body of a loop, and the new block
+    /// number that a `return` inside it ends the loop with.
+    pub fn create_block(&self) -> (State, usize) {
+        let block = self.context.block_counter.get();
+        self.context.block_counter.set(block + 1);
+        let state = self.create_child();
+        state.frame.borrow_mut().block = Some(block);
+        (state, block)
+    }
+
+    /// The block number of the innermost loop around the code of this state.
+    pub fn current_block(&self) -> Option<usize> {
+        let mut frame = self.frame.clone();
+        loop {
+            let parent = {
+                let current = frame.borrow();
+                if let Some(block) = current.block {
+                    return Some(block);
+                }
+                current.parent.clone()?
+            };
+            frame = parent;
+        }
+    }
+
+    /// Returns a state for the outermost frame, where `eval` runs code.
+    pub fn global(&self) -> State {
+        let mut frame = self.frame.clone();
+        loop {
+            let parent = match &frame.borrow().parent {
+                Some(parent) => parent.clone(),
+                None => break,
+            };
+            frame = parent;
+        }
+        Self {
+            frame,
+            context: self.context.clone(),
+        }
+    }
+
+    pub fn get_documentation(&self, name: &str, doc_type: DocType) -> Option<String> {
+        let documentation = self.context.documentation.borrow();
+        documentation.get(&(name.to_string(), doc_type)).cloned()
+    }
+
+    /// Sets the docstring of a name, or removes it when there is none.
+    pub fn set_documentation(
+        &mut self,
+        name: &str,
+        doc_type: DocType,
+        documentation: Option<String>,
+    ) {
+        let mut docs = self.context.documentation.borrow_mut();
+        match documentation {
+            Some(documentation) => docs.insert((name.to_string(), doc_type), documentation),
+            None => docs.remove(&(name.to_string(), doc_type)),
+        };
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<Function> {
+        let mut frame = self.frame.clone();
+        loop {
+            let parent = {
+                let current = frame.borrow();
+                if let Some(function) = current.functions.get(name) {
+                    return Some(function.clone());
+                }
+                current.parent.clone()?
+            };
+            frame = parent;
+        }
+    }
+
+    pub fn get_value(&self, name: &str) -> Option<LispValue> {
+        if self.is_special(name) {
+            return self.global().frame.borrow().variables.get(name).cloned();
+        }
+
+        let mut frame = self.frame.clone();
+        loop {
+            let parent = {
+                let current = frame.borrow();
+                if let Some(value) = current.variables.get(name) {
+                    return Some(value.clone());
+                }
+                current.parent.clone()?
+            };
+            frame = parent;
         }
-        value
     }
 
     pub fn add_function(&mut self, name: String, function: Function) {
-        self.functions.insert(name, function);
+        self.frame.borrow_mut().functions.insert(name, function);
     }
 
-    pub fn add_variable(&mut self, name: String, value: LispValue) {
-        self.variables.insert(name, value);
+    /// Binds a variable in the current frame. Special variables are bound
+    /// dynamically instead: their global value is replaced until
+    /// `restore_specials` is called for this frame.
+    pub fn add_variable(&mut self, name: String, value: LispValue) -> Result<(), LispError> {
+        self.check_not_constant(&name)?;
+        if self.is_special(&name) {
+            let old = self
+                .global()
+                .frame
+                .borrow_mut()
+                .variables
+                .insert(name.clone(), value);
+            self.frame.borrow_mut().saved.push((name, old));
+            return Ok(());
+        }
+        self.frame.borrow_mut().variables.insert(name, value);
+        Ok(())
     }
-}
 
-pub fn convert_to_int_array(array: Vec<LispValue>) -> Vec<i64> {
-    let mut result_array = Vec::new();
-    for value in array {
-        match value {
-            LispValue::Int(i) => {
-                result_array.push(i);
-            }
-            value => {
-                println!("{:?}", value);
-            }
+    /// Returns the value of a constant. Keywords are constants whose value
+    /// is the keyword itself.
+    pub fn get_constant(&self, name: &str) -> Option<LispValue> {
+        if name.starts_with(':') {
+            return Some(LispValue::Name(name.to_string()));
         }
+        self.context.constants.borrow().get(name).cloned()
     }
-    return result_array;
-}
 
-pub fn convert_to_float_array(array: Vec<LispValue>) -> Vec<f64> {
-    let mut result_array = Vec::new();
-    for value in array {
-        match value {
-            LispValue::Int(i) => {
-                result_array.push(i as f64);
-            }
-            LispValue::Float(f) => {
-                result_array.push(f);
-            }
-            _ => {}
+    fn check_not_constant(&self, name: &str) -> Result<(), LispError> {
+        match self.get_constant(name) {
+            Some(_) => Err(LispError::Constant(name.to_string())),
+            None => Ok(()),
         }
     }
-    return result_array;
-}
 
-pub fn convert_to_boolean_array(array: Vec<LispValue>) -> Vec<bool> {
-    let mut result_array = Vec::new();
-    for value in array {
-        match value {
-            LispValue::Boolean(b) => {
-                result_array.push(b);
+    /// Defines a constant. Defining it again is only allowed with an equal
+    /// value.
+    pub fn define_constant(&mut self, name: String, value: LispValue) -> Result<(), LispError> {
+        let mut constants = self.context.constants.borrow_mut();
+        match constants.get(&name) {
+            Some(old) if *old != value => Err(LispError::Constant(name)),
+            _ if name.starts_with(':') => Err(LispError::Constant(name)),
+            _ => {
+                constants.insert(name, value);
+                Ok(())
             }
-            _ => {}
         }
     }
-    return result_array;
-}
 
-pub fn convert_to_string_array(array: Vec<LispValue>) -> Vec<String> {
-    let mut result_array = Vec::new();
-    for value in array {
-        match value {
-            LispValue::Int(i) => {
-                result_array.push(i.to_string());
-            }
-            LispValue::Float(f) => {
-                result_array.push(f.to_string());
-            }
-            LispValue::Boolean(b) => {
-                result_array.push(b.to_string());
-            }
-            LispValue::String(s) => {
-                result_array.push(s);
+    pub fn is_special(&self, name: &str) -> bool {
+        self.context.specials.borrow().contains(name)
+    }
+
+    /// Declares `name` a special variable, and gives it `value` globally when
+    /// `overwrite` is set or it has no global value yet.
+    pub fn define_special(
+        &mut self,
+        name: String,
+        value: Option<LispValue>,
+        overwrite: bool,
+    ) -> Result<(), LispError> {
+        self.check_not_constant(&name)?;
+        self.context.specials.borrow_mut().insert(name.clone());
+        let global = self.global();
+        let mut frame = global.frame.borrow_mut();
+        if let Some(value) = value {
+            if overwrite || !frame.variables.contains_key(&name) {
+                frame.variables.insert(name, value);
             }
-            LispValue::Name(_) | LispValue::Function(_) => {}
+        }
+        Ok(())
+    }
+
+    pub fn has_special_bindings(&self) -> bool {
+        !self.frame.borrow().saved.is_empty()
+    }
+
+    /// Ends the dynamic bindings made in this frame.
+    pub fn restore_specials(&mut self) {
+        let saved: Vec<_> = self.frame.borrow_mut().saved.drain(..).collect();
+        if saved.is_empty() {
+            return;
+        }
+
+        let global = self.global();
+        let mut frame = global.frame.borrow_mut();
+        for (name, value) in saved.into_iter().rev() {
+            match value {
+                Some(value) => frame.variables.insert(name, value),
+                None => frame.variables.remove(&name),
+            };
         }
     }
 
-    return result_array;
+    /// Assigns to the closest existing binding of `name`. Unbound names and
+    /// special variables are assigned in the global frame.
+    pub fn set_variable(&mut self, name: String, value: LispValue) -> Result<(), LispError> {
+        self.check_not_constant(&name)?;
+        if self.is_special(&name) {
+            self.global()
+                .frame
+                .borrow_mut()
+                .variables
+                .insert(name, value);
+            return Ok(());
+        }
+
+        let mut frame = self.frame.clone();
+        loop {
+            let parent = {
+                let mut current = frame.borrow_mut();
+                if let Some(variable) = current.variables.get_mut(name.as_str()) {
+                    *variable = value;
+                    return Ok(());
+                }
+                match &current.parent {
+                    Some(parent) => parent.clone(),
+                    None => {
+                        current.variables.insert(name, value);
+                        return Ok(());
+                    }
+                }
+            };
+            frame = parent;
+        }
+    }
 }
 
-pub fn get_args_unified_type(args: &Vec<LispValue>) -> Type {
-    let mut value_type = Type::None;
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.frame, &other.frame)
+    }
+}
 
-    for value in args {
-        value_type = get_value_type(value_type, value);
+impl fmt::Debug for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "State({:p})", Rc::as_ptr(&self.frame))
     }
-    value_type
 }
 
-fn get_value_type(last_type: Type, value: &LispValue) -> Type {
+/// How values are written: the radix of rationals from `*print-base*`,
+/// whether it is marked from `*print-radix*`, whether strings are quoted as
+/// `write` does instead of written as they are like `princ`, and the float
+/// format written without an exponent marker.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrintOptions {
+    pub base: u32,
+    pub radix: bool,
+    pub escape: bool,
+    pub float_format: FloatFormat,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            base: 10,
+            radix: false,
+            escape: false,
+            float_format: FloatFormat::Double,
+        }
+    }
+}
+
+/// The prefix that marks a radix, like `#x` for 16.
+fn radix_prefix(base: u32) -> String {
+    match base {
+        2 => "#b".to_string(),
+        8 => "#o".to_string(),
+        16 => "#x".to_string(),
+        _ => format!("#{}r", base),
+    }
+}
+
+fn write_integer(integer: &BigInt, options: PrintOptions) -> String {
+    let digits = integer.to_string_radix(options.base);
+    match (options.radix, options.base) {
+        (false, _) => digits,
+        (true, 10) => format!("{}.", digits),
+        (true, base) => format!("{}{}", radix_prefix(base), digits),
+    }
+}
+
+/// Formats a value the way `print` shows it.
+pub fn to_print_string(value: &LispValue) -> String {
+    write_to_string(value, PrintOptions::default())
+}
+
+pub fn write_to_string(value: &LispValue, options: PrintOptions) -> String {
+    let write = |value: &LispValue| write_to_string(value, options);
     match value {
-        LispValue::Int(_) => match last_type {
-            Type::None | Type::Int => Type::Int,
-            Type::Boolean | Type::String => Type::String,
-            _ => last_type,
-        },
-        LispValue::Float(_) => match last_type {
-            Type::Int | Type::Float | Type::None => Type::Float,
-            _ => Type::String,
-        },
-        LispValue::Boolean(_) => match last_type {
-            Type::None | Type::Boolean => Type::Boolean,
-            _ => Type::String,
+        LispValue::Int(i) if options == PrintOptions::default() => i.to_string(),
+        LispValue::Int(i) => write_integer(&BigInt::from(*i), options),
+        LispValue::BigInt(i) => write_integer(i, options),
+        LispValue::Ratio(r) => match options.radix {
+            true => format!(
+                "{}{}",
+                radix_prefix(options.base),
+                r.to_string_radix(options.base)
+            ),
+            false => r.to_string_radix(options.base),
         },
-        LispValue::String(_) => Type::String,
-        LispValue::Name(_) | LispValue::Function(_) => last_type,
+        LispValue::Float(f) => format_float(*f, FloatFormat::Double, options.float_format),
+        LispValue::SingleFloat(f) => {
+            format_float(*f as f64, FloatFormat::Single, options.float_format)
+        }
+        LispValue::Complex(c) => format!("#C({} {})", write(&c.real), write(&c.imag)),
+        LispValue::Boolean(b) => b.to_string(),
+        LispValue::String(s) if options.escape => {
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        LispValue::String(s) => s.clone(),
+        LispValue::Name(n) => n.clone(),
+        LispValue::Function(list) if list.is_empty() => "nil".to_string(),
+        LispValue::Function(list) => {
+            let items: Vec<String> = list.iter().map(write).collect();
+            format!("({})", items.join(" "))
+        }
+        LispValue::DottedList(list, tail) => {
+            let items: Vec<String> = list.iter().map(write).collect();
+            format!("({} . {})", items.join(" "), write(tail))
+        }
+        LispValue::Lambda(_) => "#<FUNCTION>".to_string(),
+        LispValue::RandomState(_) => "#<RANDOM-STATE>".to_string(),
+        LispValue::Values(values) => {
+            let items: Vec<String> = values.iter().map(write).collect();
+            items.join("; ")
+        }
     }
 }
 
@@ -192,60 +750,169 @@ pub fn execute_function(
     state: &mut State,
     function_name: &str,
     arguments: Vec<LispValue>,
-) -> Option<LispValue> {
-    let mut function = None;
+) -> LispResult {
+    match state.get_function(function_name) {
+        Some(f) => execute(state, f, arguments),
+        None => Err(LispError::UndefinedFunction(function_name.to_string())),
+    }
+}
 
-    if let Some(f) = state.get_function(function_name) {
-        function = Some(f.clone());
+/// Every value but nil counts as true. In boolean compatibility mode
+/// `false` is false as well.
+pub fn is_true(state: &State, value: &LispValue) -> bool {
+    match value {
+        LispValue::Boolean(false) => !state.boolean_compatibility(),
+        value => !value.is_nil(),
     }
+}
 
-    if let Some(f) = function {
-        return execute(state, f, arguments);
+fn lookup_variable(state: &State, name: String) -> LispResult {
+    match state.get_value(name.as_str()) {
+        Some(value) => Ok(value),
+        None if state.boolean_compatibility() && name == "true" => Ok(LispValue::Boolean(true)),
+        None if state.boolean_compatibility() && name == "false" => Ok(LispValue::Boolean(false)),
+        None => Err(LispError::UnboundVariable(name)),
     }
+}
 
-    None
+/// Evaluates a form for a single value, the primary one when it returns
+/// multiple values.
+pub fn evaluate(state: &mut State, value: LispValue) -> LispResult {
+    evaluate_values(state, value).map(LispValue::primary)
 }
 
-pub fn replace_variables_with_values(state: &State, args: &mut Vec<LispValue>) {
-    for i in 0..args.len() {
-        let value = &args[i];
-        match value {
-            LispValue::Name(name) => {
-                if let Some(value) = state.get_value(name.as_str()) {
-                    args[i] = value.clone();
+/// Evaluates a single value: names are replaced with their values and
+/// function forms are executed. Constants, like `t` and keywords, are
+/// replaced without looking through the scopes. Multiple values of the form
+/// are kept.
+pub fn evaluate_values(state: &mut State, value: LispValue) -> LispResult {
+    match value {
+        LispValue::Name(name) => match state.get_constant(name.as_str()) {
+            Some(value) => Ok(value),
+            None => lookup_variable(state, name),
+        },
+        LispValue::Function(mut arguments) => {
+            if arguments.is_empty() {
+                return Ok(LispValue::Function(arguments));
+            }
+
+            match arguments.remove(0) {
+                LispValue::Name(name) => execute_function(state, name.as_str(), arguments),
+                LispValue::Function(lambda)
+                    if lambda.first() == Some(&LispValue::Name("lambda".to_string())) =>
+                {
+                    if let Some(LispValue::Lambda(function)) =
+                        create_lambda(state, lambda[1..].to_vec())
+                    {
+                        let arguments = evaluate_arguments(state, arguments)?;
+                        return call(state, *function, arguments);
+                    }
+                    arguments.insert(0, LispValue::Function(lambda));
+                    Err(LispError::IllegalFunctionCall(LispValue::Function(
+                        arguments,
+                    )))
+                }
+                head => {
+                    arguments.insert(0, head);
+                    Err(LispError::IllegalFunctionCall(LispValue::Function(
+                        arguments,
+                    )))
                 }
             }
-            _ => {}
         }
+        value => Ok(value),
     }
 }
 
-pub fn execute_functions(state: &mut State, parent_arguments: &mut Vec<LispValue>) {
-    for i in 0..parent_arguments.len() {
-        let mut is_function = false;
-        {
-            let value = &parent_arguments[i];
-            if let LispValue::Function(_) = value {
-                is_function = true;
-            }
+pub fn evaluate_arguments(
+    state: &mut State,
+    args: Vec<LispValue>,
+) -> Result<Vec<LispValue>, LispError> {
+    let mut values = Vec::new();
+    for arg in args {
+        values.push(evaluate(state, arg)?);
+    }
+    Ok(values)
+}
+
+/// Evaluates a form in tail position. Calls to custom functions, and to the
+/// functions given to `funcall` or `apply`, are returned without executing
+/// them.
+pub fn evaluate_tail(state: &mut State, value: LispValue) -> Result<Tail, LispError> {
+    let mut arguments = match value {
+        LispValue::Function(arguments) if !arguments.is_empty() => arguments,
+        value => return Ok(Tail::Value(evaluate(state, value)?)),
+    };
+
+    let function = match arguments.remove(0) {
+        LispValue::Name(name) => match state.get_function(name.as_str()) {
+            Some(function) => function,
+            None => return Err(LispError::UndefinedFunction(name)),
+        },
+        head => {
+            arguments.insert(0, head);
+            let value = evaluate_values(state, LispValue::Function(arguments))?;
+            return Ok(Tail::Value(value));
         }
+    };
 
-        if is_function {
-            let value = parent_arguments.remove(i);
-            if let LispValue::Function(mut args) = value {
-                if !args.is_empty() {
-                    if let LispValue::Name(name) = args.remove(0) {
-                        if let Some(returned_value) = execute_function(state, name.as_str(), args) {
-                            parent_arguments.insert(i, returned_value);
-                        }
-                    }
-                }
-            }
+    match function {
+        Function::Custom(..) => Ok(Tail::Call(function, evaluate_arguments(state, arguments)?)),
+        Function::Funcall
+        | Function::Apply
+        | Function::Macro(..)
+        | Function::If
+        | Function::Progn
+        | Function::Or
+        | Function::And
+        | Function::MultipleValueBind
+        | Function::Let
+        | Function::LetStar => tail_form(state, function, arguments),
+        function => Ok(Tail::Value(execute(state, function, arguments)?)),
+    }
+}
+
+/// Evaluates a form that leaves its last form in tail position. It is kept
+/// out of `evaluate_tail` because its stack frame is large.
+#[inline(never)]
+fn tail_form(
+    state: &mut State,
+    function: Function,
+    arguments: Vec<LispValue>,
+) -> Result<Tail, LispError> {
+    match function {
+        Function::Funcall | Function::Apply => funcall_tail(state, function, arguments),
+        Function::Macro(lambda_list, body, environment) => {
+            let expansion = expand_macro(state, lambda_list, body, environment, arguments)?;
+            evaluate_tail(state, expansion)
         }
+        Function::If => if_block(state, arguments),
+        Function::Progn => progn(state, arguments),
+        Function::Or => or(state, arguments),
+        Function::And => and(state, arguments),
+        Function::MultipleValueBind => multiple_value_bind(state, arguments),
+        Function::Let => let_block(state, arguments, false),
+        Function::LetStar => let_block(state, arguments, true),
+        function => Ok(Tail::Value(execute(state, function, arguments)?)),
     }
 }
 
-pub fn prepare_execution(state: &mut State, args: &mut Vec<LispValue>) {
-    replace_variables_with_values(state, args);
-    execute_functions(state, args);
+/// Evaluates the arguments of `funcall` or `apply` in tail position and
+/// returns the call of the function they give.
+fn funcall_tail(
+    state: &mut State,
+    function: Function,
+    arguments: Vec<LispValue>,
+) -> Result<Tail, LispError> {
+    let arguments = evaluate_arguments(state, arguments)?;
+    let (function, arguments) = designated_call(state, function, arguments)?;
+    Ok(Tail::Call(function, arguments))
+}
+
+/// Runs a call left over by `evaluate_tail`.
+pub fn finish_tail(state: &mut State, tail: Tail) -> LispResult {
+    match tail {
+        Tail::Value(value) => Ok(value),
+        Tail::Call(function, arguments) => call(state, function, arguments),
+    }
 }
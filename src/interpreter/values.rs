@@ -0,0 +1,84 @@
+use super::functions::*;
+use super::types::*;
+use crate::parser::LispValue;
+
+fn values_of(value: LispValue) -> Vec<LispValue> {
+    match value {
+        LispValue::Values(values) => values,
+        value => vec![value],
+    }
+}
+
+pub fn values(mut args: Vec<LispValue>) -> LispResult {
+    if args.len() == 1 {
+        return Ok(args.remove(0));
+    }
+    Ok(LispValue::Values(args))
+}
+
+/// Binds the values of a form to variables, nil for missing values, and runs
+/// the body with them.
+pub fn multiple_value_bind(state: &mut State, mut args: Vec<LispValue>) -> Result<Tail, LispError> {
+    if args.len() < 2 {
+        return Ok(Tail::Value(LispValue::nil()));
+    }
+
+    let names = match args.remove(0) {
+        LispValue::Function(names) => names,
+        _ => return Ok(Tail::Value(LispValue::nil())),
+    };
+
+    let mut values = values_of(evaluate_values(state, args.remove(0))?).into_iter();
+    let mut local_state = state.create_child();
+    for name in names {
+        let bound = match name {
+            LispValue::Name(name) => {
+                local_state.add_variable(name, values.next().unwrap_or_else(LispValue::nil))
+            }
+            _ => Ok(()),
+        };
+        if let Err(error) = bound {
+            local_state.restore_specials();
+            return Err(error);
+        }
+    }
+
+    scope_body(&mut local_state, args)
+}
+
+pub fn multiple_value_list(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 1 {
+        return Ok(LispValue::nil());
+    }
+
+    let values = values_of(evaluate_values(state, args.remove(0))?);
+    Ok(LispValue::Function(values))
+}
+
+/// Calls a function with all the values of every form as arguments.
+pub fn multiple_value_call(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    check_argument_count(&args, 1, None)?;
+
+    let designator = evaluate(state, args.remove(0))?;
+    let function = function_designator(state, "multiple-value-call", designator)?;
+
+    let mut arguments = Vec::new();
+    for form in args {
+        arguments.extend(values_of(evaluate_values(state, form)?));
+    }
+    call(state, function, arguments)
+}
+
+pub fn nth_value(state: &mut State, mut args: Vec<LispValue>) -> LispResult {
+    if args.len() != 2 {
+        return Ok(LispValue::nil());
+    }
+
+    let n = match evaluate(state, args.remove(0))? {
+        LispValue::Int(n) if n >= 0 => n as usize,
+        value => return Err(type_error("nth-value", "unsigned integer", value)),
+    };
+
+    let values = values_of(evaluate_values(state, args.remove(0))?);
+    Ok(values.into_iter().nth(n).unwrap_or_else(LispValue::nil))
+}
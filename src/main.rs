@@ -1,7 +1,7 @@
 mod interpreter;
 mod parser;
 
-use interpreter::run;
+use interpreter::Interpreter;
 
 use std::env;
 use std::fs::File;
@@ -15,12 +15,43 @@ fn main() -> std::io::Result<()> {
     let mut contents = String::new();
 
     file.read_to_string(&mut contents)?;
-    if let Some(arg) = args.next() {
+
+    let mut max_depth = None;
+    let mut boolean_compatibility = false;
+    let mut seed = None;
+    while let Some(arg) = args.next() {
         if arg == "-d" {
             parser::parse_and_print(contents.as_str());
+            return Ok(());
+        } else if arg == "--booleans" {
+            boolean_compatibility = true;
+        } else if arg == "--max-depth" {
+            match args.next().map(|depth| depth.parse::<usize>()) {
+                Some(Ok(depth)) => max_depth = Some(depth),
+                _ => {
+                    eprintln!("--max-depth expects a positive number");
+                    return Ok(());
+                }
+            }
+        } else if arg == "--seed" {
+            match args.next().map(|seed| seed.parse::<u64>()) {
+                Some(Ok(value)) => seed = Some(value),
+                _ => {
+                    eprintln!("--seed expects a non-negative number");
+                    return Ok(());
+                }
+            }
         }
-    } else {
-        run(contents.as_str());
     }
+
+    let mut interpreter = Interpreter::new();
+    if let Some(depth) = max_depth {
+        interpreter.set_max_depth(depth);
+    }
+    interpreter.set_boolean_compatibility(boolean_compatibility);
+    if let Some(seed) = seed {
+        interpreter.set_random_seed(seed);
+    }
+    interpreter.run(contents.as_str());
     Ok(())
 }
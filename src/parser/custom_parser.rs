@@ -1,23 +1,90 @@
-use super::LispValue;
+use super::{LispValue, ReadOptions};
+use crate::interpreter::{BigInt, FloatFormat, Ratio};
 
-fn parse_value(value: &str) -> LispValue {
-    if let Ok(i) = value.parse::<i64>() {
+/// Reads a float written the Common Lisp way: decimal digits with a point,
+/// an exponent or both. The exponent marker `e` gives a float in
+/// `default`, `s` and `f` single floats, and `d` and `l` double floats.
+fn parse_float(value: &str, default: FloatFormat) -> Option<LispValue> {
+    let (mantissa, exponent, format) = match value.find(|c: char| "esfdlESFDL".contains(c)) {
+        Some(index) => {
+            let format = match value[index..].chars().next()?.to_ascii_lowercase() {
+                'e' => default,
+                's' | 'f' => FloatFormat::Single,
+                _ => FloatFormat::Double,
+            };
+            (&value[..index], Some(&value[index + 1..]), format)
+        }
+        None => (value, None, default),
+    };
+
+    let unsigned = |text: &str| text.strip_prefix(['+', '-']).unwrap_or(text).to_string();
+    let digits = unsigned(mantissa);
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole.to_string(), Some(fraction.to_string())),
+        None => (digits, None),
+    };
+    let all_digits = |text: &str| text.chars().all(|c| c.is_ascii_digit());
+    let valid_mantissa = match &fraction {
+        Some(fraction) if exponent.is_none() => {
+            all_digits(&whole) && !fraction.is_empty() && all_digits(fraction)
+        }
+        Some(fraction) => {
+            all_digits(&whole) && all_digits(fraction) && whole.len() + fraction.len() > 0
+        }
+        None => exponent.is_some() && !whole.is_empty() && all_digits(&whole),
+    };
+    let valid_exponent = exponent.is_none_or(|exponent| {
+        let exponent = unsigned(exponent);
+        !exponent.is_empty() && all_digits(&exponent)
+    });
+    if !valid_mantissa || !valid_exponent {
+        return None;
+    }
+
+    let text = match exponent {
+        Some(exponent) => format!("{}e{}", mantissa, exponent),
+        None => mantissa.to_string(),
+    };
+    match format {
+        FloatFormat::Single => text.parse::<f32>().ok().map(LispValue::SingleFloat),
+        FloatFormat::Double => text.parse::<f64>().ok().map(LispValue::Float),
+    }
+}
+
+/// Reads an atom. Integers and ratios are read in the read base, except that
+/// a trailing dot marks a decimal integer. Floats are always decimal.
+fn parse_value(value: &str, options: ReadOptions) -> LispValue {
+    if let (10, Ok(i)) = (options.base, value.parse::<i64>()) {
         return LispValue::Int(i);
     }
 
-    if let Ok(f) = value.parse::<f64>() {
-        return LispValue::Float(f);
+    if let Some(i) = BigInt::parse_radix(value, options.base) {
+        return LispValue::integer(i);
+    }
+
+    if let Some(i) = value.strip_suffix('.').and_then(BigInt::parse) {
+        return LispValue::integer(i);
+    }
+
+    if let Some(ratio) = Ratio::parse(value, options.base) {
+        return LispValue::rational(ratio);
+    }
+
+    if let Some(float) = parse_float(value, options.float_format) {
+        return float;
     }
 
-    if value == "false" {
-        return LispValue::Boolean(false);
-    } else if value == "true" {
-        return LispValue::Boolean(true);
+    if value == "nil" {
+        return LispValue::nil();
     }
 
     LispValue::Name(value.to_string())
 }
 
+/// A form read from the start of some code, with the index of its last
+/// character, or a message saying why it could not be read.
+type Read = Result<(LispValue, usize), String>;
+
 fn find_endline(code: &[char]) -> usize {
     let mut count = 0;
     for c in code {
@@ -26,63 +93,84 @@ fn find_endline(code: &[char]) -> usize {
         }
         count += 1;
     }
-    return count;
+    count
 }
 
-fn read_argument(code: &[char]) -> (LispValue, usize) {
+fn unexpected(c: char) -> String {
+    match c {
+        ')' => "unmatched close parenthesis".to_string(),
+        c => format!("unexpected \"{}\"", c),
+    }
+}
+
+/// Reads an atom or a string. The count is the index of the character that
+/// ends it, except that a parenthesis or a comment right after an atom is
+/// left for the list to read.
+fn read_argument(code: &[char], options: ReadOptions) -> Read {
+    if code.is_empty() {
+        return Err("end of file".to_string());
+    }
+
     let mut arg = String::new();
     let mut quate_open = false;
     let mut is_string = false;
-    let mut size = 0;
+    let mut size: usize = 0;
     for c in code {
         match c {
             '(' | ')' => {
-                    if quate_open {
-                        arg.push(*c);
-                    } else {
-                        size -= 1;
-                        break;
-                    }
-                },
+                if quate_open {
+                    arg.push(*c);
+                } else {
+                    size = size.checked_sub(1).ok_or_else(|| unexpected(*c))?;
+                    break;
+                }
+            }
             '"' => {
                 if quate_open {
                     break;
                 }
                 quate_open = true;
                 is_string = true;
-            },
+            }
             '\n' => {
-                break;
-            },
+                if quate_open {
+                    arg.push(*c);
+                } else {
+                    break;
+                }
+            }
             ' ' | '\t' => {
                 if quate_open {
                     arg.push(*c);
                 } else {
                     break;
                 }
-            },
+            }
             ';' => {
                 if quate_open {
                     arg.push(*c);
                 } else {
-                    size -= 1;
+                    size = size.checked_sub(1).ok_or_else(|| unexpected(*c))?;
                     break;
                 }
             }
             _ => {
                 arg.push(*c);
-            },
+            }
         }
         size += 1;
     }
     if is_string {
-        return (LispValue::String(arg), size);
+        if code.get(size) != Some(&'"') {
+            return Err("end of file in string".to_string());
+        }
+        return Ok((LispValue::String(arg), size));
     }
 
-    (parse_value(&arg), size)
+    Ok((parse_value(&arg, options), size))
 }
 
-fn parse_function(code: &[char]) -> (LispValue, usize) {
+fn parse_function(code: &[char], options: ReadOptions) -> Read {
     let mut is_open = false;
     let mut read_chars_count = 0;
     let mut arguments = Vec::new();
@@ -90,25 +178,36 @@ fn parse_function(code: &[char]) -> (LispValue, usize) {
         match code[read_chars_count] {
             '(' => {
                 if is_open {
-                    let (value, count) = parse_function(&code[read_chars_count..]);
+                    let (value, count) = parse_function(&code[read_chars_count..], options)?;
                     arguments.push(value);
                     read_chars_count += count;
                 }
                 is_open = true;
-            },
+            }
             ')' => {
-                break;
-            },
+                return Ok((read_dotted_list(arguments), read_chars_count));
+            }
+            '#' if is_complex(&code[read_chars_count..]) => {
+                let (value, count) = read_complex(&code[(read_chars_count + 2)..], options)?;
+                arguments.push(value);
+                read_chars_count += count + 2;
+            }
+            '#' if code.get(read_chars_count + 1) == Some(&'\'') => {
+                let (value, count) = read_function_quote(&code[(read_chars_count + 2)..], options)?;
+                arguments.push(value);
+                read_chars_count += count + 2;
+            }
+            '\'' => {
+                let (value, count) = read_quote(&code[(read_chars_count + 1)..], options)?;
+                arguments.push(value);
+                read_chars_count += count + 1;
+            }
             ';' => {
-                read_chars_count += 1;
-                if code[read_chars_count] == ';' {
-                    let count = find_endline(code);
-                    read_chars_count += count;
-                }
-            },
-            ' ' | '\t' | '\n' => {},
+                read_chars_count += find_endline(&code[read_chars_count..]);
+            }
+            ' ' | '\t' | '\n' => {}
             _ => {
-                let (value, count) = read_argument(&code[read_chars_count..]);
+                let (value, count) = read_argument(&code[read_chars_count..], options)?;
                 arguments.push(value);
                 read_chars_count += count;
             }
@@ -116,68 +215,179 @@ fn parse_function(code: &[char]) -> (LispValue, usize) {
         read_chars_count += 1;
     }
 
-    (LispValue::Function(arguments), read_chars_count)
+    Err("end of file in list".to_string())
+}
+
+/// Turns `(a b . c)` into a dotted list.
+fn read_dotted_list(mut arguments: Vec<LispValue>) -> LispValue {
+    let dot = LispValue::Name(".".to_string());
+    if arguments.len() < 3 || arguments[arguments.len() - 2] != dot {
+        return LispValue::Function(arguments);
+    }
+
+    let tail = arguments.pop().unwrap_or_else(LispValue::nil);
+    arguments.pop();
+    LispValue::DottedList(arguments, Box::new(tail))
 }
 
+/// Reads the form after a `'` or `#'` prefix, which may follow whitespace.
+fn read_form(code: &[char], options: ReadOptions) -> Read {
+    match code.first() {
+        Some('(') => parse_function(code, options),
+        Some('\'') => {
+            let (value, count) = read_quote(&code[1..], options)?;
+            Ok((value, count + 1))
+        }
+        Some('#') if is_complex(code) => {
+            let (value, count) = read_complex(&code[2..], options)?;
+            Ok((value, count + 2))
+        }
+        Some('#') if code.get(1) == Some(&'\'') => {
+            let (value, count) = read_function_quote(&code[2..], options)?;
+            Ok((value, count + 2))
+        }
+        Some(c) if c.is_whitespace() => {
+            let skipped = code.iter().take_while(|c| c.is_whitespace()).count();
+            let (value, count) = read_form(&code[skipped..], options)?;
+            Ok((value, count + skipped))
+        }
+        _ => read_argument(code, options),
+    }
+}
+
+fn is_complex(code: &[char]) -> bool {
+    matches!(code.get(1), Some('C') | Some('c')) && code.get(2) == Some(&'(')
+}
+
+/// Reads the parts of `#C(real imag)`. Anything but two reals is an error.
+fn read_complex(code: &[char], options: ReadOptions) -> Read {
+    let (value, count) = parse_function(code, options)?;
+    let is_real = |value: &LispValue| {
+        matches!(
+            value,
+            LispValue::Int(_)
+                | LispValue::BigInt(_)
+                | LispValue::Ratio(_)
+                | LispValue::Float(_)
+                | LispValue::SingleFloat(_)
+        )
+    };
+    match value {
+        LispValue::Function(parts) if parts.len() == 2 && parts.iter().all(is_real) => Ok((
+            LispValue::complex(parts[0].clone(), parts[1].clone()),
+            count,
+        )),
+        _ => Err("illegal complex number format".to_string()),
+    }
+}
+
+fn read_function_quote(code: &[char], options: ReadOptions) -> Read {
+    let (value, count) = read_form(code, options)?;
+    let function_name = LispValue::Name("function".to_string());
+    Ok((LispValue::Function(vec![function_name, value]), count))
+}
+
+fn read_quote(code: &[char], options: ReadOptions) -> Read {
+    let (value, count) = read_form(code, options)?;
+    let quote = LispValue::Name("quote".to_string());
+    Ok((LispValue::Function(vec![quote, value]), count))
+}
+
+/// Splits code into its top level lists. Parentheses in strings and
+/// comments do not count.
 fn split_functions(code: &[char]) -> Vec<&[char]> {
     let mut open_count = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
     let mut current_function = (0, 0);
     let mut functions = Vec::new();
     for i in 0..code.len() {
         match code[i] {
+            '\n' if in_comment => in_comment = false,
+            _ if in_comment => {}
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            ';' => in_comment = true,
             '(' => {
                 open_count += 1;
                 if open_count == 1 {
                     current_function.0 = i;
                 }
-            },
-            ')' => {
+            }
+            ')' if open_count > 0 => {
                 open_count -= 1;
                 if open_count == 0 {
                     current_function.1 = i + 1;
                     functions.push(&code[current_function.0..current_function.1]);
                 }
-            },
-            _ => {},
+            }
+            _ => {}
         };
     }
-    return functions;
+    // An unfinished last form is kept, so reading it reports the error.
+    if open_count > 0 {
+        functions.push(&code[current_function.0..]);
+    }
+    functions
 }
 
-pub fn parse(code: &str) -> Option<Vec<LispValue>> {
-    let chars : Vec<char> = code.chars().collect();
+/// Reads the parenthesized forms of `code`, or the error of the first one
+/// that cannot be read.
+pub fn parse(code: &str, options: ReadOptions) -> Result<Vec<LispValue>, String> {
+    let chars: Vec<char> = code.chars().collect();
     let functions = split_functions(&chars[..]);
     let mut lisp_functions = Vec::new();
 
     for f in functions {
-        let (value, _) = parse_function(f);
+        let (value, _) = parse_function(f, options)?;
         lisp_functions.push(value);
     }
-    
-    Some(lisp_functions) 
-}
 
-pub fn parse_and_print(code: &str) {
-    let chars : Vec<char> = code.chars().collect();
-    let functions = split_functions(&chars[..]);
-    let mut lisp_functions = Vec::new();
+    Ok(lisp_functions)
+}
 
-    for f in functions {
-        let (value, _) = parse_function(f);
-        lisp_functions.push(value);
+/// Reads the first form of `text`, which may also be an atom. Returns it
+/// with the index of the first character after it, counting a whitespace
+/// that ends an atom, or None when there is no form before the end of the
+/// text. Text that starts a form but does not finish it is an error.
+pub fn read_from_string(
+    text: &str,
+    options: ReadOptions,
+) -> Result<Option<(LispValue, usize)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut start = 0;
+    while start < chars.len() {
+        match chars[start] {
+            ';' => start += find_endline(&chars[start..]),
+            c if c.is_whitespace() => start += 1,
+            _ => break,
+        }
+    }
+    if start == chars.len() {
+        return Ok(None);
     }
-    
-    for f in &lisp_functions {
-        println!("{}", f);
+
+    let (value, count) = read_form(&chars[start..], options)?;
+    Ok(Some((value, (start + count + 1).min(chars.len()))))
+}
+
+pub fn parse_and_print(code: &str) {
+    match parse(code, ReadOptions::default()) {
+        Ok(functions) => {
+            for f in &functions {
+                println!("{}", f);
+            }
+        }
+        Err(error) => println!("Parse error: {}", error),
     }
 }
 
 #[cfg(test)]
 mod custom_parser_test {
 
-    use super::super::{LispValue, ToLispValue};
-    use super::parse;
-    
+    use super::super::{LispValue, ReadOptions, ToLispValue};
+    use super::{parse, FloatFormat};
+
     macro_rules! lf {
         ($n:tt $($a:expr) *) => {
             LispValue::Function(vec![ln!($n),
@@ -198,52 +408,247 @@ mod custom_parser_test {
     macro_rules! ln {
         ($n:tt) => {
             LispValue::Name(stringify!($n).to_string())
-        }
+        };
     }
 
     #[test]
     fn function_call() {
-        if let Some(result) = parse("(+ 3 4 5)") {
+        if let Ok(result) = parse("(+ 3 4 5)", ReadOptions::default()) {
             assert_eq!(result, vlf!(+ 3 4 5));
         } else {
-            panic!("Parse retern None")
+            panic!("Parse retern Err")
         }
 
-        if let Some(result) = parse("(/= 3.0 4.0 5.0)") {
+        if let Ok(result) = parse("(/= 3.0 4.0 5.0)", ReadOptions::default()) {
             assert_eq!(result, vlf!(/= 3.0 4.0 5.0));
         } else {
-            panic!("Parse retern None")
+            panic!("Parse retern Err")
         }
 
-        if let Some(result) = parse("(print \"Test\")") {
+        if let Ok(result) = parse("(print \"Test\")", ReadOptions::default()) {
             assert_eq!(result, vlf!(print "Test"));
         } else {
-            panic!("Parse retern None")
+            panic!("Parse retern Err")
         }
     }
 
     #[test]
     fn function_inside_function() {
-        if let Some(result) = parse("(+ 3 (- 6 5))") {
+        if let Ok(result) = parse("(+ 3 (- 6 5))", ReadOptions::default()) {
             assert_eq!(result, vlf!(+ 3 lf!(- 6 5)));
         }
 
-        if let Some(result) = parse("(+ (- 6 5.0) (+ 3.0 4))") {
+        if let Ok(result) = parse("(+ (- 6 5.0) (+ 3.0 4))", ReadOptions::default()) {
             assert_eq!(result, vlf!(+ lf!(- 6 5.0) lf!(+ 3.0 4)));
         }
     }
-    
+
     #[test]
     fn function_definition() {
-        if let Some(result) = parse(r#"(defun println ()
-                                       (print " "))"#) {
-            assert_eq!(result, vlf!(defun ln!(println) LispValue::Function(vec![]) lf!(print " ")))
+        if let Ok(result) = parse(
+            r#"(defun println ()
+                                       (print " "))"#,
+            ReadOptions::default(),
+        ) {
+            assert_eq!(
+                result,
+                vlf!(defun ln!(println) LispValue::Function(vec![]) lf!(print " "))
+            )
         }
 
-        if let Some(result) = parse(r#"(defun square (n) 
+        if let Ok(result) = parse(
+            r#"(defun square (n) 
                                        (print "squaring")
-                                       (* n n))"#) {
-            assert_eq!(result, vlf!(defun ln!(square) lf!(n) lf!(print "squaring") lf!(* ln!(n) ln!(n))))
+                                       (* n n))"#,
+            ReadOptions::default(),
+        ) {
+            assert_eq!(
+                result,
+                vlf!(defun ln!(square) lf!(n) lf!(print "squaring") lf!(* ln!(n) ln!(n)))
+            )
+        }
+    }
+
+    #[test]
+    fn function_quote() {
+        if let Ok(result) = parse("(funcall #'+ 1 2)", ReadOptions::default()) {
+            assert_eq!(result, vlf!(funcall lf!(function ln!(+)) 1 2));
+        } else {
+            panic!("Parse retern Err")
+        }
+
+        if let Ok(result) = parse("(mapcar #'(lambda (x) x) y)", ReadOptions::default()) {
+            assert_eq!(
+                result,
+                vlf!(mapcar lf!(function lf!(lambda lf!(x) ln!(x))) ln!(y))
+            );
+        } else {
+            panic!("Parse retern Err")
         }
     }
+
+    #[test]
+    fn nil() {
+        if let Ok(result) = parse("(list nil () t)", ReadOptions::default()) {
+            assert_eq!(result, vlf!(list LispValue::nil() LispValue::nil() ln!(t)));
+        } else {
+            panic!("Parse retern Err")
+        }
+    }
+
+    #[test]
+    fn dotted_list() {
+        if let Ok(result) = parse("(cons (a . b) (1 2 . rest))", ReadOptions::default()) {
+            let pair = LispValue::DottedList(vec![ln!(a)], Box::new(ln!(b)));
+            let list = LispValue::DottedList(
+                vec![LispValue::Int(1), LispValue::Int(2)],
+                Box::new(ln!(rest)),
+            );
+            assert_eq!(result, vlf!(cons pair list));
+        } else {
+            panic!("Parse retern Err")
+        }
+    }
+
+    #[test]
+    fn quote() {
+        if let Ok(result) = parse("(list 'a '(1 b) ''c '#'d)", ReadOptions::default()) {
+            let list = LispValue::Function(vec![LispValue::Int(1), ln!(b)]);
+            let quoted_function = lf!(quote lf!(function ln!(d)));
+            assert_eq!(
+                result,
+                vlf!(list lf!(quote ln!(a)) lf!(quote list) lf!(quote lf!(quote ln!(c))) quoted_function)
+            );
+        } else {
+            panic!("Parse retern Err")
+        }
+    }
+
+    #[test]
+    fn complex() {
+        if let Ok(result) = parse("(list #C(1 2) #c(1.5 0) #C(3 0))", ReadOptions::default()) {
+            let complex = LispValue::complex(LispValue::Int(1), LispValue::Int(2));
+            let float_complex = LispValue::complex(LispValue::Float(1.5), LispValue::Float(0.0));
+            assert_eq!(result, vlf!(list complex float_complex 3));
+        } else {
+            panic!("Parse retern Err")
+        }
+        for code in [
+            "(list '#C(a b))",
+            "(list #C(1))",
+            "(list #C(1 2 3))",
+            "(list #C())",
+            "(list #C(#C(1 2) 3))",
+        ] {
+            assert_eq!(
+                parse(code, ReadOptions::default()),
+                Err("illegal complex number format".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn read_base() {
+        if let Ok(result) = parse(
+            "(list ff -10 10. a/2 1.5 add)",
+            ReadOptions {
+                base: 16,
+                ..ReadOptions::default()
+            },
+        ) {
+            let numbers = [255, -16, 10, 5].iter().map(|i| LispValue::Int(*i));
+            let mut list = vec![ln!(list)];
+            list.extend(numbers);
+            list.extend(vec![LispValue::Float(1.5), LispValue::Int(2781)]);
+            assert_eq!(result, vec![LispValue::Function(list)]);
+        } else {
+            panic!("Parse retern Err")
+        }
+    }
+
+    #[test]
+    fn floats() {
+        if let Ok(result) = parse(
+            "(list 1.5 -.5 2e3 1.5f0 1.5d0 1s-1 +2.0L1 1.e2 e2 1.5e inf nan)",
+            ReadOptions::default(),
+        ) {
+            let mut list = vec![ln!(list)];
+            list.extend(vec![
+                LispValue::Float(1.5),
+                LispValue::Float(-0.5),
+                LispValue::Float(2000.0),
+            ]);
+            list.extend(vec![
+                LispValue::SingleFloat(1.5),
+                LispValue::Float(1.5),
+                LispValue::SingleFloat(0.1),
+            ]);
+            list.extend(vec![LispValue::Float(20.0), LispValue::Float(100.0)]);
+            list.extend(vec![
+                ln!(e2),
+                LispValue::Name("1.5e".to_string()),
+                ln!(inf),
+                ln!(nan),
+            ]);
+            assert_eq!(result, vec![LispValue::Function(list)]);
+        } else {
+            panic!("Parse retern Err")
+        }
+
+        let single = ReadOptions {
+            float_format: FloatFormat::Single,
+            ..ReadOptions::default()
+        };
+        if let Ok(result) = parse("(list 1.5 1.5e0 1.5d0)", single) {
+            let floats = vec![
+                ln!(list),
+                LispValue::SingleFloat(1.5),
+                LispValue::SingleFloat(1.5),
+                LispValue::Float(1.5),
+            ];
+            assert_eq!(result, vec![LispValue::Function(floats)]);
+        } else {
+            panic!("Parse retern Err")
+        }
+    }
+
+    #[test]
+    fn strings_and_comments() {
+        if let Ok(result) = parse(
+            "(print \"(\") ; (ignored\n(print \")\")",
+            ReadOptions::default(),
+        ) {
+            let open = vec![ln!(print), LispValue::String("(".to_string())];
+            let close = vec![ln!(print), LispValue::String(")".to_string())];
+            assert_eq!(
+                result,
+                vec![LispValue::Function(open), LispValue::Function(close)]
+            );
+        } else {
+            panic!("Parse retern Err")
+        }
+
+        if let Ok(result) = parse("(list ; (comment\n \"a (b\" 1)", ReadOptions::default()) {
+            let list = vec![
+                ln!(list),
+                LispValue::String("a (b".to_string()),
+                LispValue::Int(1),
+            ];
+            assert_eq!(result, vec![LispValue::Function(list)]);
+        } else {
+            panic!("Parse retern Err")
+        }
+    }
+
+    #[test]
+    fn unfinished_forms() {
+        assert_eq!(
+            parse("(print 1) (list 1 2", ReadOptions::default()),
+            Err("end of file in list".to_string())
+        );
+        assert_eq!(
+            parse("(list 1 ')", ReadOptions::default()),
+            Err("unmatched close parenthesis".to_string())
+        );
+    }
 }
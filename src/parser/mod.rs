@@ -4,37 +4,91 @@ mod nom_parser;
 #[cfg(feature = "custom")]
 mod custom_parser;
 
+use crate::interpreter::{
+    to_print_string, BigInt, Complex, FloatFormat, Function, RandomState, Ratio,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum LispValue {
     String(String),
     Boolean(bool),
     Int(i64),
+    /// An integer outside the range of `Int`. Integers that fit are always
+    /// stored as `Int`.
+    BigInt(BigInt),
+    /// A fraction whose denominator is not one. Ratios that are integers are
+    /// always stored as integers.
+    Ratio(Ratio),
+    /// A double float.
     Float(f64),
+    SingleFloat(f32),
+    /// A complex number. Rational complex numbers with a zero imaginary part
+    /// are always stored as their real part.
+    Complex(Box<Complex>),
     Name(String),
     Function(Vec<LispValue>),
+    DottedList(Vec<LispValue>, Box<LispValue>),
+    Lambda(Box<Function>),
+    /// A random number generator, shared by every value that refers to it.
+    RandomState(Rc<RefCell<RandomState>>),
+    /// Multiple values returned by a form. Never stored in variables or lists.
+    Values(Vec<LispValue>),
+}
+
+impl LispValue {
+    /// The empty list, which is also the only false value.
+    pub fn nil() -> LispValue {
+        LispValue::Function(vec![])
+    }
+
+    /// The canonical true value.
+    pub fn t() -> LispValue {
+        LispValue::Name("t".to_string())
+    }
+
+    /// An integer, stored as `Int` when it fits.
+    pub fn integer(value: BigInt) -> LispValue {
+        match value.to_i64() {
+            Some(i) => LispValue::Int(i),
+            None => LispValue::BigInt(value),
+        }
+    }
+
+    /// A rational number, stored as an integer when the denominator is one.
+    pub fn rational(value: Ratio) -> LispValue {
+        match value.is_integer() {
+            true => LispValue::integer(value.numerator().clone()),
+            false => LispValue::Ratio(value),
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        match self {
+            LispValue::Function(list) => list.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The first of multiple values, or nil when there are none. Other values
+    /// are returned unchanged.
+    pub fn primary(self) -> LispValue {
+        match self {
+            LispValue::Values(values) => values.into_iter().next().unwrap_or_else(LispValue::nil),
+            value => value,
+        }
+    }
 }
 
 impl std::fmt::Display for LispValue {
+    /// Writes the value as the Lisp printer does, like `princ-to-string`.
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-       match self {
-            LispValue::String(s) => write!(f, "\"{}\"", s),
-            LispValue::Boolean(true) => write!(f, "true"),
-            LispValue::Boolean(false) => write!(f, "false"),
-            LispValue::Int(n) => write!(f, "int({})", n),
-            LispValue::Float(n) => write!(f, "float({})", n),
-            LispValue::Name(n) => write!(f, "{}", n),
-            LispValue::Function(args) => {
-                write!(f, "( ")?;
-                for a in args {
-                    write!(f, "{} ", a)?;
-                }
-                write!(f, ")")
-            }
-       } 
-
+        write!(f, "{}", to_print_string(self))
     }
 }
 
+#[allow(dead_code)]
 pub trait ToLispValue<T> {
     fn get(t: T) -> LispValue;
 }
@@ -69,9 +123,22 @@ impl ToLispValue<LispValue> for LispValue {
     }
 }
 
-#[cfg(feature = "custom")]
-pub fn parse(code: &str) -> Option<Vec<LispValue>> {
-    custom_parser::parse(code)
+/// How numbers are read: the radix of integers and ratios from
+/// `*read-base*`, and the format of floats without an exponent marker from
+/// `*read-default-float-format*`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReadOptions {
+    pub base: u32,
+    pub float_format: FloatFormat,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            base: 10,
+            float_format: FloatFormat::Double,
+        }
+    }
 }
 
 #[cfg(feature = "custom")]
@@ -79,7 +146,27 @@ pub fn parse_and_print(code: &str) {
     custom_parser::parse_and_print(code);
 }
 
+/// Reads the first form of `text`, returning it with the index after it, or
+/// None when the text has no form. A form that is not finished is an error.
+#[cfg(feature = "custom")]
+pub fn read_from_string(
+    text: &str,
+    options: ReadOptions,
+) -> Result<Option<(LispValue, usize)>, String> {
+    custom_parser::read_from_string(text, options)
+}
+
 #[cfg(feature = "nom")]
-pub fn parse(code: &str) -> Option<Vec<LispValue>> {
-    nom_parser::parse(code)
+pub fn read_from_string(
+    text: &str,
+    _options: ReadOptions,
+) -> Result<Option<(LispValue, usize)>, String> {
+    let mut forms = nom_parser::parse(&format!("({})", text))
+        .ok_or_else(|| format!("cannot read \"{}\"", text))?;
+    match forms.pop() {
+        Some(LispValue::Function(values)) => {
+            Ok(values.into_iter().next().map(|value| (value, text.len())))
+        }
+        _ => Ok(None),
+    }
 }
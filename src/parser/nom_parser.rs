@@ -156,5 +156,4 @@ pub fn parse(code: &str) -> Option<Vec<LispValue>> {
         Ok(s) => Some(s.1),
         _ => None,
     }
-
-}
\ No newline at end of file
+}